@@ -0,0 +1,185 @@
+use grid::{Grid, Pos};
+
+/// Warehouse packed one bit per cell, each row padded to whole 64 bit words.
+///
+/// Neighbour counts are computed for 64 cells at a time: the eight neighbour bitsets of a word
+/// are obtained with shifts of the row above, the row itself and the row below, then summed
+/// into four bit planes with a bit-sliced adder. Only the default rules are supported, i.e. the
+/// eight surrounding cells with empty space beyond the edges, but any threshold.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BitGrid {
+    height: usize,
+    stride: usize,
+    words: Vec<u64>,
+}
+
+impl BitGrid {
+    pub fn from_grid(grid: &Grid<bool>) -> Self {
+        let stride = grid.width().div_ceil(64);
+        let mut words = vec![0; stride * grid.height()];
+        for ((r, c), &roll) in grid.iter() {
+            if roll {
+                words[r * stride + c / 64] |= 1 << (c % 64);
+            }
+        }
+        BitGrid {
+            height: grid.height(),
+            stride,
+            words,
+        }
+    }
+
+    fn row(&self, r: usize) -> &[u64] {
+        &self.words[r * self.stride..(r + 1) * self.stride]
+    }
+
+    /// Mask of rolls in `row` with fewer than `threshold` neighbouring rolls
+    fn accessible_row(&self, [above, row, below]: [&[u64]; 3], threshold: usize, out: &mut [u64]) {
+        // neighbours of cell c at c-1 and c+1, carrying bits across word boundaries
+        let shifted = |line: &[u64], i: usize| {
+            let prev = if i > 0 { line[i - 1] } else { 0 };
+            let next = if i + 1 < self.stride { line[i + 1] } else { 0 };
+            ((line[i] << 1) | (prev >> 63), (line[i] >> 1) | (next << 63))
+        };
+        for i in 0..self.stride {
+            // sum the three cells above and below, and the two beside, as two bit numbers
+            let (l, r) = shifted(above, i);
+            let (a0, a1) = full_add(l, above[i], r);
+            let (l, r) = shifted(below, i);
+            let (b0, b1) = full_add(l, below[i], r);
+            let (l, r) = shifted(row, i);
+            let (m0, m1) = (l ^ r, l & r);
+            // then the three two bit numbers into bit planes of the count, 0 to 8
+            let (ones, carry) = full_add(a0, b0, m0);
+            let (t, fours) = full_add(a1, b1, m1);
+            let (twos, carry) = (t ^ carry, t & carry);
+            let planes = [ones, twos, fours ^ carry, fours & carry];
+            out[i] = row[i] & less_than(&planes, threshold);
+        }
+    }
+
+    /// Mask of accessible rolls in the rows flagged in `rows`, other rows of `mask` are cleared.
+    /// Returns the number of accessible rolls.
+    fn accessible(&self, threshold: usize, rows: &[bool], mask: &mut [u64]) -> usize {
+        let zeros = vec![0; self.stride];
+        let mut n = 0;
+        for (r, out) in mask.chunks_mut(self.stride).enumerate() {
+            if !rows[r] {
+                out.fill(0);
+                continue;
+            }
+            let above = if r > 0 { self.row(r - 1) } else { &zeros };
+            let below = if r + 1 < self.height {
+                self.row(r + 1)
+            } else {
+                &zeros
+            };
+            self.accessible_row([above, self.row(r), below], threshold, out);
+            n += out.iter().map(|w| w.count_ones() as usize).sum::<usize>();
+        }
+        n
+    }
+
+    pub fn pick_rolls(&self, threshold: usize) -> Vec<Pos> {
+        let mut mask = vec![0; self.words.len()];
+        self.accessible(threshold, &vec![true; self.height], &mut mask);
+        let mut picked = Vec::new();
+        for (i, &word) in mask.iter().enumerate() {
+            let (r, base) = (i / self.stride, (i % self.stride) * 64);
+            let mut word = word;
+            while word != 0 {
+                picked.push((r, base + word.trailing_zeros() as usize));
+                word &= word - 1;
+            }
+        }
+        picked
+    }
+
+    /// Remove accessible rolls round by round, calling `on_round` with the number removed in each round
+    pub fn remove_rolls(&mut self, threshold: usize, mut on_round: impl FnMut(usize)) -> usize {
+        let mut mask = vec![0; self.words.len()];
+        // only rows next to a removal can have new accessible rolls
+        let mut dirty = vec![true; self.height];
+        let mut n = 0;
+        loop {
+            let removed = self.accessible(threshold, &dirty, &mut mask);
+            if removed == 0 {
+                break;
+            }
+            n += removed;
+            on_round(removed);
+            dirty.fill(false);
+            for (r, (words, picked)) in self
+                .words
+                .chunks_mut(self.stride)
+                .zip(mask.chunks(self.stride))
+                .enumerate()
+            {
+                if picked.iter().all(|&w| w == 0) {
+                    continue;
+                }
+                for (word, picked) in words.iter_mut().zip(picked) {
+                    *word &= !picked;
+                }
+                dirty[r.saturating_sub(1)..(r + 2).min(self.height)].fill(true);
+            }
+        }
+        n
+    }
+}
+
+/// Sum and carry bits of three one bit values in every lane
+fn full_add(a: u64, b: u64, c: u64) -> (u64, u64) {
+    let t = a ^ b;
+    (t ^ c, (a & b) | (t & c))
+}
+
+/// Lanes of the bit-sliced counter holding a value less than `threshold`
+fn less_than(planes: &[u64; 4], threshold: usize) -> u64 {
+    if threshold > 15 {
+        return !0;
+    }
+    let (mut less, mut equal) = (0, !0);
+    for (i, plane) in planes.iter().enumerate().rev() {
+        if threshold >> i & 1 == 1 {
+            less |= equal & !plane;
+            equal &= plane;
+        } else {
+            equal &= !plane;
+        }
+    }
+    less
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::{Edges, Neighbourhood, Rules};
+
+    #[test]
+    fn matches_grid() {
+        let input = std::fs::read_to_string("src/inputs/test1.txt").expect("io error");
+        let sample = Grid::parse(&input, |c| c == '@').expect("Parse Error!");
+        // rolls in diagonal bands, wide enough that rows span three words
+        let bands = Grid::from_vec(
+            130,
+            70,
+            (0..130 * 70)
+                .map(|i| (i / 130 + i % 130 / 3) % 7 < 4)
+                .collect(),
+        );
+        let full = Grid::new(65, 3, true);
+        for grid in [sample, bands, full] {
+            for threshold in [1, 4, 6, 9] {
+                let rules = Rules::new(threshold, Neighbourhood::Moore(1), Edges::Empty);
+                let bits = BitGrid::from_grid(&grid);
+                assert_eq!(bits.pick_rolls(threshold), crate::pick_rolls(&grid, &rules));
+                let mut expected = grid.clone();
+                let n = crate::part2(&mut expected, &rules, |_| ());
+                let mut bits = bits;
+                assert_eq!(bits.remove_rolls(threshold, |_| ()), n);
+                assert_eq!(bits, BitGrid::from_grid(&expected));
+            }
+        }
+    }
+}
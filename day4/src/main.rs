@@ -1,97 +1,206 @@
+mod bitgrid;
+mod render;
+mod rounds;
+mod rules;
+
+use bitgrid::BitGrid;
+use grid::{Grid, Pos};
+use render::Render;
+use rounds::RoundMap;
+use rules::{Edges, Neighbourhood, Rules};
 use std::env;
 use std::error::Error;
-use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
 use std::time::Instant;
 
-fn parse(filename: &str) -> Result<Vec<Vec<bool>>, std::io::Error> {
-    let file = File::open(filename)?;
-    let reader = BufReader::new(file);
-    let grid = reader
-        .lines()
-        .map(|line| line.unwrap().chars().map(|c| c == '@').collect())
-        .collect::<Vec<_>>();
-    // println!("grid={grid:?}");
+fn parse(filename: &str) -> Result<Grid<bool>, Box<dyn Error>> {
+    let input = fs::read_to_string(filename)?;
+    let grid = Grid::parse(&input, |c| c == '@')?;
+    // println!("grid:\n{}", grid.render(|&roll| if roll { '@' } else { '.' }));
     Ok(grid)
 }
 
-fn pick_rolls(grid: &[Vec<bool>]) -> Vec<(usize,usize)> {
-    let mut picked = Vec::new();
-    let directions: [(isize, isize); 8] = [
-        (-1, -1),
-        (-1, 0),
-        (-1, 1),
-        (0, -1),
-        (0, 1),
-        (1, -1),
-        (1, 0),
-        (1, 1),
-    ];
-    let (rows, cols) = (grid.len(), grid[0].len());
-    for r in 0..rows {
-        for c in 0..cols {
-            if !grid[r][c] {
-                // no rolls to remove
-                continue;
-            }
-            let mut rolls = 0;
-            for (dr, dc) in directions {
-                let (adjr, adjc) = (r as isize + dr, c as isize + dc);
-		if adjr < 0 || adjc < 0 { // lower bound 
-		    continue;
-		}
-		let (adjr, adjc) = (adjr as usize, adjc as usize);
-		if adjr >= rows || adjc >= cols { // uppper bound
-		    continue;
-		}
-		if grid[adjr][adjc] {
-                    rolls += 1;
-		    if rolls >= 4 {
-			break;
-                    }
-                };
-            }
-            if rolls < 4 {
-                // println!("{r},{c}");
-                picked.push((r, c));
-            }
-        }
-    }
-    picked
+fn pick_rolls(grid: &Grid<bool>, rules: &Rules) -> Vec<Pos> {
+    grid.positions()
+        .filter(|&pos| rules.accessible(grid, pos))
+        .collect()
 }
 
-fn part1(grid: &[Vec<bool>]) -> usize {
-    pick_rolls(grid).len()
+fn part1(grid: &Grid<bool>, rules: &Rules) -> usize {
+    pick_rolls(grid, rules).len()
 }
 
-fn part2(grid: &mut [Vec<bool>]) -> usize {
+/// Remove accessible rolls round by round, calling `on_round` with the rolls removed in each round
+fn part2(grid: &mut Grid<bool>, rules: &Rules, mut on_round: impl FnMut(&[Pos])) -> usize {
+    // number of neighbouring rolls for every roll, only updated around removed rolls
+    let mut counts = Grid::new(grid.width(), grid.height(), 0);
+    for pos in grid.positions() {
+        if grid[pos] {
+            counts[pos] = rules.count_rolls(grid, pos);
+        }
+    }
     let mut n = 0;
-    loop {
-	let picked = pick_rolls(grid);
-        if picked.is_empty() {
-            break;
+    let mut queue = pick_rolls(grid, rules);
+    while !queue.is_empty() {
+        n += queue.len();
+        println!("removed={} total={n}", queue.len());
+        on_round(&queue);
+        for &pos in &queue {
+            grid[pos] = false;
+        }
+        let mut next = Vec::new();
+        for pos in queue {
+            for adj in rules.neighbours(grid, pos).flatten() {
+                if !grid[adj] {
+                    continue;
+                }
+                counts[adj] -= 1;
+                if counts[adj] + 1 == rules.threshold {
+                    // just dropped below the threshold, accessible next round
+                    next.push(adj);
+                }
+            }
         }
-	n += picked.len();
-        println!("removed={} total={n}", picked.len());
-	for (r, c) in picked {
-            grid[r][c] = false;
-	}
+        queue = next;
     }
     n
 }
 
+/// Both parts on the bit-packed grid, for very large warehouses
+fn run_bits(grid: &Grid<bool>, rules: &Rules) -> Result<(), Box<dyn Error>> {
+    if rules.neighbourhood != Neighbourhood::Moore(1) || rules.edges != Edges::Empty {
+        return Err("--bits only supports the default neighbourhood and edges".into());
+    }
+    let start0 = Instant::now();
+    let mut bits = BitGrid::from_grid(grid);
+    println!("packed, time: {:?}", start0.elapsed());
+    let start1 = Instant::now();
+    let n1 = bits.pick_rolls(rules.threshold).len();
+    let duration1 = start1.elapsed();
+    println!("part1: {n1}, time: {duration1:?}");
+    let start2 = Instant::now();
+    let mut n = 0;
+    let n2 = bits.remove_rolls(rules.threshold, |removed| {
+        n += removed;
+        println!("removed={removed} total={n}");
+    });
+    let duration2 = start2.elapsed();
+    println!("part2: {n2}, time: {duration2:?}");
+    Ok(())
+}
+
 fn main() -> Result<(), Box<dyn Error>> {
-    let filename = env::args()
-        .nth(1)
+    let (rules, args) = Rules::from_args(env::args().skip(1))?;
+    let (render, args) = Render::from_args(args.into_iter())?;
+    let (mut bits, mut summary, mut round_map) = (false, false, None);
+    let mut rest = Vec::new();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--bits" => bits = true,
+            "--summary" => summary = true,
+            "--round-map" => round_map = Some(args.next().ok_or("--round-map needs a value")?),
+            _ => rest.push(arg),
+        }
+    }
+    let filename = rest
+        .into_iter()
+        .next()
         .unwrap_or_else(|| "inputs/input.txt".to_string());
     let mut grid = parse(&filename)?;
+    if bits {
+        if render.enabled() || summary || round_map.is_some() {
+            return Err("--bits doesn't record rounds".into());
+        }
+        return run_bits(&grid, &rules);
+    }
+    let initial = grid.clone();
+    let mut rounds = RoundMap::new(grid.width(), grid.height());
     let start1 = Instant::now();
-    let n1 = part1(&grid);
+    let n1 = part1(&grid, &rules);
     let duration1 = start1.elapsed();
     println!("part1: {n1}, time: {duration1:?}");
     let start2 = Instant::now();
-    let n2 = part2(&mut grid);
+    let n2 = part2(&mut grid, &rules, |removed| rounds.record(removed));
     let duration2 = start2.elapsed();
     println!("part2: {n2}, time: {duration2:?}");
+    if summary {
+        println!("{}", rounds.summary(&grid, &rules));
+    }
+    if let Some(filename) = round_map {
+        let mut out = BufWriter::new(File::create(&filename)?);
+        rounds.write(&mut out, &grid)?;
+        out.flush()?;
+        println!("wrote round map to {filename}");
+    }
+    render.run(&initial, &rounds)?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sample() {
+        let input = "\
+..@@.@@@@.
+@@@.@.@.@@
+@@@@@.@.@@
+@.@@@@..@.
+@@.@@@@.@@
+.@@@@@@@.@
+.@.@.@.@@@
+@.@@@.@@@@
+.@@@@@@@@.
+@.@.@@@.@.
+";
+        let mut grid = Grid::parse(input, |c| c == '@').expect("Parse Error!");
+        let rules = Rules::default();
+        assert_eq!(part1(&grid, &rules), 13);
+        let mut rounds = RoundMap::new(grid.width(), grid.height());
+        assert_eq!(
+            part2(&mut grid, &rules, |removed| rounds.record(removed)),
+            43
+        );
+        let summary = rounds.summary(&grid, &rules);
+        assert_eq!(summary.rounds, 9);
+        assert_eq!(summary.largest_round, Some((1, 13)));
+        assert_eq!((summary.core_size, summary.core_components), (28, 1));
+        assert_eq!(rounds.label((3, 3)), Some(9));
+        assert_eq!(rounds.label((3, 4)), None);
+    }
+
+    #[test]
+    fn rule_variants() {
+        let input = fs::read_to_string("src/inputs/test1.txt").expect("io error");
+        let grid = Grid::parse(&input, |c| c == '@').expect("Parse Error!");
+        for rules in [
+            Rules::new(4, Neighbourhood::Moore(1), Edges::Full),
+            Rules::new(2, Neighbourhood::VonNeumann(1), Edges::Empty),
+            Rules::new(9, Neighbourhood::Moore(2), Edges::Wrap),
+        ] {
+            // the work queue must agree with rescanning the whole grid every round
+            let mut rescanned = grid.clone();
+            let mut expected = 0;
+            loop {
+                let picked = pick_rolls(&rescanned, &rules);
+                if picked.is_empty() {
+                    break;
+                }
+                expected += picked.len();
+                for pos in picked {
+                    rescanned[pos] = false;
+                }
+            }
+            assert_eq!(
+                part2(&mut grid.clone(), &rules, |_| ()),
+                expected,
+                "{rules:?}"
+            );
+        }
+        let rules = Rules::new(4, Neighbourhood::Moore(1), Edges::Full);
+        assert_eq!(part1(&grid, &rules), 1);
+    }
+}
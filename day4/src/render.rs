@@ -0,0 +1,135 @@
+use crate::rounds::RoundMap;
+use grid::Grid;
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+use std::thread;
+use std::time::Duration;
+
+const EMPTY: [u8; 3] = [0, 0, 0];
+const ROLL: [u8; 3] = [200, 200, 200];
+
+/// Visualization of the removal rounds.
+///
+/// Frames are written as binary PPM images, `frame-0000.ppm` being the initial warehouse and
+/// frame k the warehouse after round k. They can be stitched into an animated GIF with e.g.
+/// `convert -delay 10 frames/*.ppm erosion.gif`.
+#[derive(Debug, Default)]
+pub struct Render {
+    frames: Option<PathBuf>,
+    scale: usize,
+    replay: bool,
+    delay: Duration,
+}
+
+impl Render {
+    /// Parse rendering flags from command line arguments, returning the remaining arguments
+    pub fn from_args(
+        mut args: impl Iterator<Item = String>,
+    ) -> Result<(Self, Vec<String>), Box<dyn Error>> {
+        let mut render = Render {
+            scale: 4,
+            delay: Duration::from_millis(200),
+            ..Default::default()
+        };
+        let mut rest = Vec::new();
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or_else(|| format!("{arg} needs a value"));
+            match arg.as_str() {
+                "--frames" => render.frames = Some(value()?.into()),
+                "--scale" => render.scale = value()?.parse()?,
+                "--replay" => render.replay = true,
+                "--delay" => render.delay = Duration::from_millis(value()?.parse()?),
+                _ => rest.push(arg),
+            }
+        }
+        Ok((render, rest))
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.frames.is_some() || self.replay
+    }
+
+    pub fn run(&self, initial: &Grid<bool>, rounds: &RoundMap) -> Result<(), Box<dyn Error>> {
+        if let Some(dir) = &self.frames {
+            fs::create_dir_all(dir)?;
+            let total = rounds.sizes().len();
+            for k in 0..=total {
+                let path = dir.join(format!("frame-{k:04}.ppm"));
+                let mut out = BufWriter::new(File::create(&path)?);
+                write_ppm(&mut out, initial, rounds, k, self.scale)?;
+                out.flush()?;
+            }
+            println!("wrote {} frames to {}", total + 1, dir.display());
+        }
+        if self.replay {
+            replay(initial, rounds, self.delay)?;
+        }
+        Ok(())
+    }
+}
+
+/// Colour for a roll removed in `round` of `total`, from red for early rounds to blue for late ones
+fn round_colour(round: usize, total: usize) -> [u8; 3] {
+    let t = if total > 1 {
+        (round - 1) as f64 / (total - 1) as f64
+    } else {
+        0.0
+    };
+    // hue from 0 (red) to 240 (blue) degrees at full saturation and value
+    let h = t * 4.0;
+    let x = ((1.0 - (h % 2.0 - 1.0).abs()) * 255.0) as u8;
+    match h as usize {
+        0 => [255, x, 0],
+        1 => [x, 255, 0],
+        2 => [0, 255, x],
+        _ => [0, x, 255],
+    }
+}
+
+/// Write the warehouse after `frame` rounds, rolls removed so far coloured by their round
+fn write_ppm(
+    out: &mut impl Write,
+    initial: &Grid<bool>,
+    rounds: &RoundMap,
+    frame: usize,
+    scale: usize,
+) -> io::Result<()> {
+    let total = rounds.sizes().len();
+    initial.write_ppm(out, scale, |pos, &roll| match (roll, rounds.label(pos)) {
+        (false, _) => EMPTY,
+        (true, Some(round)) if round <= frame => round_colour(round, total),
+        (true, _) => ROLL,
+    })
+}
+
+/// Replay the removal rounds in the terminal, highlighting the rolls removed in each round
+fn replay(initial: &Grid<bool>, rounds: &RoundMap, delay: Duration) -> io::Result<()> {
+    let sizes = rounds.sizes();
+    let mut stdout = io::stdout().lock();
+    for frame in 0..=sizes.len() {
+        // clear the screen and move the cursor home
+        write!(stdout, "\x1b[2J\x1b[H")?;
+        let removed_now = frame.checked_sub(1).map_or(0, |i| sizes[i]);
+        writeln!(
+            stdout,
+            "round {frame}/{} removed={removed_now}",
+            sizes.len()
+        )?;
+        for r in 0..initial.height() {
+            for c in 0..initial.width() {
+                match (initial[(r, c)], rounds.label((r, c))) {
+                    (false, _) => write!(stdout, ".")?,
+                    (true, Some(round)) if round == frame => write!(stdout, "\x1b[31mx\x1b[0m")?,
+                    (true, Some(round)) if round < frame => write!(stdout, ".")?,
+                    (true, _) => write!(stdout, "@")?,
+                }
+            }
+            writeln!(stdout)?;
+        }
+        stdout.flush()?;
+        thread::sleep(delay);
+    }
+    Ok(())
+}
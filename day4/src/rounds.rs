@@ -0,0 +1,124 @@
+use crate::rules::Rules;
+use grid::{Grid, Pos};
+use std::fmt;
+use std::io::{self, Write};
+
+/// Round in which each roll becomes accessible and is removed, recorded while removing rolls
+#[derive(Debug, Clone)]
+pub struct RoundMap {
+    /// 1-based removal round, `None` for empty cells and the stable core
+    labels: Grid<Option<usize>>,
+    /// number of rolls removed in each round
+    sizes: Vec<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Summary {
+    pub rounds: usize,
+    pub removed: usize,
+    /// first round with the most removals and its size
+    pub largest_round: Option<(usize, usize)>,
+    pub core_size: usize,
+    pub core_components: usize,
+    pub largest_component: usize,
+}
+
+impl RoundMap {
+    pub fn new(width: usize, height: usize) -> Self {
+        RoundMap {
+            labels: Grid::new(width, height, None),
+            sizes: Vec::new(),
+        }
+    }
+
+    /// Record the rolls removed in the next round
+    pub fn record(&mut self, removed: &[Pos]) {
+        self.sizes.push(removed.len());
+        for &pos in removed {
+            self.labels[pos] = Some(self.sizes.len());
+        }
+    }
+
+    pub fn label(&self, pos: Pos) -> Option<usize> {
+        self.labels[pos]
+    }
+
+    pub fn sizes(&self) -> &[usize] {
+        &self.sizes
+    }
+
+    /// Summary statistics, `core` being the warehouse left once nothing more can be removed
+    pub fn summary(&self, core: &Grid<bool>, rules: &Rules) -> Summary {
+        let mut largest_round = None;
+        for (i, &size) in self.sizes.iter().enumerate() {
+            if largest_round.is_none_or(|(_, largest)| size > largest) {
+                largest_round = Some((i + 1, size));
+            }
+        }
+        let components = components(core, rules);
+        Summary {
+            rounds: self.sizes.len(),
+            removed: self.sizes.iter().sum(),
+            largest_round,
+            core_size: components.iter().sum(),
+            core_components: components.len(),
+            largest_component: components.into_iter().max().unwrap_or(0),
+        }
+    }
+
+    /// Write the round of every roll, `#` for the stable core and `.` for empty cells,
+    /// space separated and right aligned
+    pub fn write(&self, out: &mut impl Write, core: &Grid<bool>) -> io::Result<()> {
+        let width = self.sizes.len().max(1).to_string().len();
+        for r in 0..core.height() {
+            let row = (0..core.width())
+                .map(|c| match (self.labels[(r, c)], core[(r, c)]) {
+                    (Some(round), _) => format!("{round:>width$}"),
+                    (None, true) => format!("{:>width$}", "#"),
+                    (None, false) => format!("{:>width$}", "."),
+                })
+                .collect::<Vec<_>>();
+            writeln!(out, "{}", row.join(" "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Sizes of the connected components of rolls, connected through the rules' neighbourhood
+fn components(core: &Grid<bool>, rules: &Rules) -> Vec<usize> {
+    let mut seen = Grid::new(core.width(), core.height(), false);
+    let mut sizes = Vec::new();
+    for pos in core.positions() {
+        if !core[pos] || seen[pos] {
+            continue;
+        }
+        seen[pos] = true;
+        let mut stack = vec![pos];
+        let mut size = 0;
+        while let Some(pos) = stack.pop() {
+            size += 1;
+            for adj in rules.neighbours(core, pos).flatten() {
+                if core[adj] && !seen[adj] {
+                    seen[adj] = true;
+                    stack.push(adj);
+                }
+            }
+        }
+        sizes.push(size);
+    }
+    sizes
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "rounds: {}", self.rounds)?;
+        writeln!(f, "removed: {}", self.removed)?;
+        match self.largest_round {
+            Some((round, size)) => writeln!(f, "largest round: {round} removed={size}")?,
+            None => writeln!(f, "largest round: none")?,
+        }
+        writeln!(f, "core size: {}", self.core_size)?;
+        writeln!(f, "core components: {}", self.core_components)?;
+        write!(f, "largest component: {}", self.largest_component)
+    }
+}
@@ -0,0 +1,140 @@
+use grid::{Grid, Pos};
+use std::error::Error;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Neighbourhood {
+    /// square of side 2r+1 around the roll
+    Moore(usize),
+    /// diamond of manhattan distance r around the roll
+    VonNeumann(usize),
+}
+
+/// What lies beyond the edge of the warehouse
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Edges {
+    Empty,
+    Full,
+    Wrap,
+}
+
+impl FromStr for Edges {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "empty" => Ok(Edges::Empty),
+            "full" => Ok(Edges::Full),
+            "wrap" => Ok(Edges::Wrap),
+            _ => Err(format!("unknown edges '{s}', expected empty, full or wrap")),
+        }
+    }
+}
+
+/// A roll is accessible when fewer than `threshold` of its neighbours are rolls
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rules {
+    pub threshold: usize,
+    pub neighbourhood: Neighbourhood,
+    pub edges: Edges,
+    offsets: Vec<(isize, isize)>,
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Rules::new(4, Neighbourhood::Moore(1), Edges::Empty)
+    }
+}
+
+impl Rules {
+    pub fn new(threshold: usize, neighbourhood: Neighbourhood, edges: Edges) -> Self {
+        let (r, manhattan) = match neighbourhood {
+            Neighbourhood::Moore(r) => (r as isize, false),
+            Neighbourhood::VonNeumann(r) => (r as isize, true),
+        };
+        let mut offsets = Vec::new();
+        for dr in -r..=r {
+            for dc in -r..=r {
+                if (dr, dc) == (0, 0) || (manhattan && dr.abs() + dc.abs() > r) {
+                    continue;
+                }
+                offsets.push((dr, dc));
+            }
+        }
+        Rules {
+            threshold,
+            neighbourhood,
+            edges,
+            offsets,
+        }
+    }
+
+    /// Parse rule flags from command line arguments, returning the remaining arguments
+    pub fn from_args(
+        mut args: impl Iterator<Item = String>,
+    ) -> Result<(Self, Vec<String>), Box<dyn Error>> {
+        let (mut threshold, mut shape, mut radius, mut edges) =
+            (4, "moore".to_string(), 1, Edges::Empty);
+        let mut rest = Vec::new();
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or_else(|| format!("{arg} needs a value"));
+            match arg.as_str() {
+                "--threshold" => threshold = value()?.parse()?,
+                "--neighbourhood" => shape = value()?,
+                "--radius" => radius = value()?.parse()?,
+                "--edges" => edges = value()?.parse()?,
+                _ => rest.push(arg),
+            }
+        }
+        let neighbourhood = match shape.as_str() {
+            "moore" => Neighbourhood::Moore(radius),
+            "von-neumann" => Neighbourhood::VonNeumann(radius),
+            _ => {
+                return Err(format!(
+                    "unknown neighbourhood '{shape}', expected moore or von-neumann"
+                )
+                .into());
+            }
+        };
+        Ok((Rules::new(threshold, neighbourhood, edges), rest))
+    }
+
+    /// Neighbouring positions of `pos`, `None` for neighbours beyond a non-wrapping edge.
+    /// With wrapping, a small grid can yield the same position more than once.
+    pub fn neighbours<'a, T>(
+        &'a self,
+        grid: &'a Grid<T>,
+        pos: Pos,
+    ) -> impl Iterator<Item = Option<Pos>> + 'a {
+        self.offsets.iter().map(move |&d| match self.edges {
+            Edges::Wrap => Some(grid.wrapping_offset(pos, d)),
+            Edges::Empty | Edges::Full => grid.offset(pos, d),
+        })
+    }
+
+    fn neighbouring_rolls<'a>(
+        &'a self,
+        grid: &'a Grid<bool>,
+        pos: Pos,
+    ) -> impl Iterator<Item = Option<Pos>> + 'a {
+        self.neighbours(grid, pos).filter(|adj| match adj {
+            Some(adj) => grid[*adj],
+            None => self.edges == Edges::Full,
+        })
+    }
+
+    /// Number of neighbouring rolls, counting the full edge as rolls
+    pub fn count_rolls(&self, grid: &Grid<bool>, pos: Pos) -> usize {
+        self.neighbouring_rolls(grid, pos).count()
+    }
+
+    pub fn accessible(&self, grid: &Grid<bool>, pos: Pos) -> bool {
+        // stop counting once the threshold is reached
+        grid[pos]
+            && self
+                .neighbouring_rolls(grid, pos)
+                .take(self.threshold)
+                .count()
+                < self.threshold
+    }
+}
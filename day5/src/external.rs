@@ -0,0 +1,176 @@
+use crate::{Id, Range, fresh_count, grammar};
+use intervals::IntervalSet;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// most spill files merged at once, further passes merge the merged files
+const FAN_IN: usize = 64;
+
+static SPILLS: AtomicUsize = AtomicUsize::new(0);
+
+/// Count fresh IDs with bounded memory: ranges are read `chunk_size` at a time, each chunk is
+/// sorted, merged and spilled to a file in `spill_dir`, then the spill files are k-way merged.
+/// IDs after the blank line are not needed and not read. The count is `None` when a range is
+/// open ended.
+pub fn count_fresh(
+    filename: &str,
+    chunk_size: usize,
+    spill_dir: &Path,
+) -> Result<Option<u128>, Box<dyn Error>> {
+    let reader = BufReader::new(File::open(filename)?);
+    let mut spills = Vec::new();
+    let mut chunk = Vec::with_capacity(chunk_size);
+    for (i, line) in reader.lines().enumerate() {
+        let line = line?;
+        let range = match grammar::content(&line) {
+            None => continue,
+            Some("") => break,
+            Some(s) => grammar::parse_range(s).map_err(|reason| grammar::ParseError {
+                line: i + 1,
+                text: line.clone(),
+                reason,
+            })?,
+        };
+        chunk.push(range);
+        if chunk.len() == chunk_size {
+            spills.push(spill(spill_dir, &chunk)?);
+            chunk.clear();
+        }
+    }
+    if !chunk.is_empty() {
+        spills.push(spill(spill_dir, &chunk)?);
+    }
+    // merge in passes so no more than FAN_IN files are open at once
+    while spills.len() > FAN_IN {
+        let mut merged = Vec::new();
+        for group in spills.chunks(FAN_IN) {
+            let merged_group = Spill::new(spill_dir);
+            let mut out = BufWriter::new(File::create(&merged_group.0)?);
+            for range in MergedRuns::open(group)? {
+                write_range(&mut out, range?)?;
+            }
+            out.flush()?;
+            merged.push(merged_group);
+        }
+        // dropping the merged files removes them
+        spills = merged;
+    }
+    // blocks stop at the first read error, which is returned once counting is done
+    let mut failed = Ok(());
+    let blocks =
+        MergedRuns::open(&spills)?.map_while(|range| range.map_err(|err| failed = Err(err)).ok());
+    let n = fresh_count(blocks);
+    failed?;
+    Ok(n)
+}
+
+/// Spill file, removed when dropped so no error path leaves it behind
+struct Spill(PathBuf);
+
+impl Spill {
+    /// Name for a new spill file in `spill_dir`, unique within the process
+    fn new(spill_dir: &Path) -> Self {
+        let i = SPILLS.fetch_add(1, Ordering::Relaxed);
+        Spill(spill_dir.join(format!("day5-{}-{i}.spill", process::id())))
+    }
+}
+
+impl Drop for Spill {
+    fn drop(&mut self) {
+        // nothing to do if it was never created
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+/// Merge a chunk of ranges and write the blocks, in order, to a new spill file, 32 bytes per
+/// range
+fn spill(spill_dir: &Path, chunk: &[Range]) -> io::Result<Spill> {
+    let spill = Spill::new(spill_dir);
+    let mut out = BufWriter::new(File::create(&spill.0)?);
+    for &range in IntervalSet::from_ranges(chunk.iter().copied()).ranges() {
+        write_range(&mut out, range)?;
+    }
+    out.flush()?;
+    Ok(spill)
+}
+
+fn write_range(out: &mut impl Write, (start, end): Range) -> io::Result<()> {
+    out.write_all(&start.to_le_bytes())?;
+    out.write_all(&end.to_le_bytes())
+}
+
+fn read_range(reader: &mut impl Read) -> io::Result<Option<Range>> {
+    let mut buf = [0; 32];
+    match reader.read_exact(&mut buf) {
+        Ok(()) => {
+            let (start, end) = buf.split_at(16);
+            Ok(Some((
+                Id::from_le_bytes(start.try_into().unwrap()),
+                Id::from_le_bytes(end.try_into().unwrap()),
+            )))
+        }
+        Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+/// Disjoint blocks covering the ranges of several sorted spill files, in increasing order
+struct MergedRuns {
+    readers: Vec<BufReader<File>>,
+    // next range of each reader, smallest first
+    heap: BinaryHeap<Reverse<(Range, usize)>>,
+}
+
+impl MergedRuns {
+    fn open(spills: &[Spill]) -> io::Result<Self> {
+        let mut readers = Vec::with_capacity(spills.len());
+        let mut heap = BinaryHeap::with_capacity(spills.len());
+        for (i, spill) in spills.iter().enumerate() {
+            let mut reader = BufReader::new(File::open(&spill.0)?);
+            if let Some(range) = read_range(&mut reader)? {
+                heap.push(Reverse((range, i)));
+            }
+            readers.push(reader);
+        }
+        Ok(MergedRuns { readers, heap })
+    }
+
+    fn pop(&mut self) -> io::Result<Option<Range>> {
+        let Some(Reverse((range, i))) = self.heap.pop() else {
+            return Ok(None);
+        };
+        if let Some(next) = read_range(&mut self.readers[i])? {
+            self.heap.push(Reverse((next, i)));
+        }
+        Ok(Some(range))
+    }
+
+    fn next_block(&mut self) -> io::Result<Option<Range>> {
+        let Some((start, mut end)) = self.pop()? else {
+            return Ok(None);
+        };
+        // absorb every following range that overlaps or touches the block
+        while let Some(&Reverse(((next_start, next_end), _))) = self.heap.peek() {
+            if next_start > end.saturating_add(1) {
+                break;
+            }
+            end = end.max(next_end);
+            self.pop()?;
+        }
+        Ok(Some((start, end)))
+    }
+}
+
+impl Iterator for MergedRuns {
+    type Item = io::Result<Range>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_block().transpose()
+    }
+}
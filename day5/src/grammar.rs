@@ -0,0 +1,137 @@
+//! Input grammar for the ingredient database.
+//!
+//! Fresh ranges come first, one per line, then a blank line and the available IDs. Bounds are
+//! signed integers and ranges can be written as
+//!
+//! - `3-5` or `-5--3`: inclusive bounds
+//! - `100-` and `-50`: open ended, up to the largest ID or from the smallest
+//! - `10..20`, `10..=20`, `10..` and `..20`: exclusive or inclusive end, as in Rust
+//!
+//! Anything after a `#` is a comment. Lines holding only a comment are skipped, so they don't
+//! end the ranges section.
+
+use crate::{Id, Range};
+use std::error::Error;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub line: usize,
+    pub text: String,
+    pub reason: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {} in '{}'", self.line, self.reason, self.text)
+    }
+}
+
+impl Error for ParseError {}
+
+/// Line without its comment and surrounding whitespace, `None` when it is only a comment
+pub fn content(line: &str) -> Option<&str> {
+    match line.split_once('#') {
+        Some((content, _)) if content.trim().is_empty() => None,
+        Some((content, _)) => Some(content.trim()),
+        None => Some(line.trim()),
+    }
+}
+
+fn bound(s: &str) -> Result<Id, String> {
+    let s = s.trim();
+    if s.is_empty() {
+        return Err("missing bound".to_string());
+    }
+    s.parse::<Id>()
+        .map_err(|err| format!("invalid bound '{s}' ({err})"))
+}
+
+/// Parse a range in any of the supported forms into inclusive bounds
+pub fn parse_range(s: &str) -> Result<Range, String> {
+    if let Some((start, end)) = s.split_once("..") {
+        let start = if start.trim().is_empty() {
+            Id::MIN
+        } else {
+            bound(start)?
+        };
+        let end = match end.strip_prefix('=') {
+            Some(end) => bound(end)?,
+            None if end.trim().is_empty() => Id::MAX,
+            None => bound(end)?
+                .checked_sub(1)
+                .ok_or("exclusive end below the smallest ID")?,
+        };
+        return Ok((start, end));
+    }
+    // the separating dash follows a digit, any other dash is a sign
+    let bytes = s.as_bytes();
+    let separator = (1..bytes.len()).find(|&i| bytes[i] == b'-' && bytes[i - 1].is_ascii_digit());
+    match separator {
+        Some(i) if s[i + 1..].trim().is_empty() => Ok((bound(&s[..i])?, Id::MAX)),
+        Some(i) => Ok((bound(&s[..i])?, bound(&s[i + 1..])?)),
+        None => match s.strip_prefix('-') {
+            Some(end) => Ok((Id::MIN, bound(end)?)),
+            None => Err("expected a range like 3-5, 100-, -50 or 10..20".to_string()),
+        },
+    }
+}
+
+/// Parse the ranges section and the IDs section
+pub fn parse_input(input: &str) -> Result<(Vec<Range>, Vec<Id>), ParseError> {
+    let (mut ranges, mut ids) = (Vec::new(), Vec::new());
+    let mut in_ranges = true;
+    for (i, line) in input.lines().enumerate() {
+        let error = |reason: String| ParseError {
+            line: i + 1,
+            text: line.to_string(),
+            reason,
+        };
+        match content(line) {
+            None => (),
+            Some("") if in_ranges => in_ranges = false,
+            // trailing or repeated blank lines
+            Some("") => (),
+            Some(s) if in_ranges => ranges.push(parse_range(s).map_err(error)?),
+            Some(s) => ids.push(
+                s.parse::<Id>()
+                    .map_err(|err| error(format!("invalid ID ({err})")))?,
+            ),
+        }
+    }
+    Ok((ranges, ids))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ranges() {
+        assert_eq!(parse_range("3-5"), Ok((3, 5)));
+        assert_eq!(parse_range("-5--3"), Ok((-5, -3)));
+        assert_eq!(parse_range("-5-10"), Ok((-5, 10)));
+        assert_eq!(parse_range("100-"), Ok((100, Id::MAX)));
+        assert_eq!(parse_range("-50"), Ok((Id::MIN, 50)));
+        assert_eq!(parse_range("--50"), Ok((Id::MIN, -50)));
+        assert_eq!(parse_range("10..20"), Ok((10, 19)));
+        assert_eq!(parse_range("10..=20"), Ok((10, 20)));
+        assert_eq!(parse_range("..20"), Ok((Id::MIN, 19)));
+        assert_eq!(parse_range("-3.."), Ok((-3, Id::MAX)));
+        assert!(parse_range("17").is_err());
+        assert!(parse_range("3-x").is_err());
+    }
+
+    #[test]
+    fn sections() {
+        let input = "# fresh\n3-5\n\n# available\n  \n5 # ok\n-2\n";
+        assert_eq!(parse_input(input), Ok((vec![(3, 5)], vec![5, -2])));
+        let err = parse_input("3-5\n1-2-3\n").unwrap_err();
+        assert_eq!(err.line, 2);
+        assert_eq!(
+            err.to_string(),
+            "line 2: invalid bound '2-3' (invalid digit found in string) in '1-2-3'"
+        );
+        assert_eq!(parse_input("3-5\n\nseven\n").unwrap_err().line, 3);
+    }
+}
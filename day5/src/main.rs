@@ -1,91 +1,332 @@
+mod external;
+mod grammar;
+mod report;
+
+use intervals::IntervalSet;
+use report::Report;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
 use std::env;
 use std::error::Error;
-use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::fs;
+use std::path::PathBuf;
 use std::time::Instant;
 
-fn parse(filename: &str) -> Result<(Vec<(u64,u64)>, Vec<u64>), std::io::Error> {
-    let file = File::open(filename)?;
-    let reader = BufReader::new(file);
-    let (mut ranges, mut ids) = (Vec::new(), Vec::new());
-    for line in reader.lines() {
-	let line = line?;
-	if line.is_empty() {
-	    // this just separates valid ranges from the ids
-	    continue;
-	}
-				
-        if let Some((start, end)) = line.split_once('-') {
-            ranges.push((start.parse::<u64>().unwrap(), end.parse::<u64>().unwrap()));
-        } else {
-	    ids.push(line.parse::<u64>().unwrap());
-        }
-    }
-    ranges.sort_by(|a, b| a.0.cmp(&b.0));
-	
+/// ingredient ID, signed so ranges can extend below zero
+type Id = i128;
+
+/// inclusive range of fresh ingredient IDs, open ends reach `Id::MIN` or `Id::MAX`
+type Range = (Id, Id);
+
+fn parse(filename: &str) -> Result<(Vec<Range>, Vec<Id>), Box<dyn Error>> {
+    let input = fs::read_to_string(filename)?;
+    let (mut ranges, ids) =
+        grammar::parse_input(&input).map_err(|err| format!("{filename}: {err}"))?;
+    ranges.sort();
     // println!("ranges={ranges:?}");
     Ok((ranges, ids))
 }
 
-fn part1(ranges: &[(u64,u64)], ids: &[u64]) -> u64 {
-    let mut n = 0;
+fn part1(ranges: &[Range], ids: &[Id]) -> usize {
+    // merge once, then each lookup is a binary search
+    let fresh = IntervalSet::from_ranges(ranges.iter().copied());
+    ids.iter().filter(|&&id| fresh.contains(id)).count()
+}
+
+/// Ranges covering each ID, in increasing ID order, found by sweeping the ranges sorted by
+/// start and the sorted IDs together
+fn cover(ranges: &[Range], ids: &[Id]) -> Vec<(Id, Vec<Range>)> {
+    let mut ranges = ranges.to_vec();
+    ranges.sort();
+    let mut ids = ids.to_vec();
+    ids.sort();
+    let mut next = ranges.iter().peekable();
+    // ranges started so far, ending soonest first
+    let mut active = BinaryHeap::new();
+    let mut covered = Vec::with_capacity(ids.len());
     for id in ids {
-	for (start, end) in ranges {
-	    if id >= start && id <= end {
-		n += 1;
-		break;
-	    }
-	}
-    }
-    n
+        while let Some(&&(start, end)) = next.peek() {
+            if start > id {
+                break;
+            }
+            active.push(Reverse((end, start)));
+            next.next();
+        }
+        while let Some(&Reverse((end, _))) = active.peek() {
+            if end >= id {
+                break;
+            }
+            active.pop();
+        }
+        let mut by = active
+            .iter()
+            .map(|&Reverse((end, start))| (start, end))
+            .collect::<Vec<_>>();
+        by.sort();
+        covered.push((id, by));
+    }
+    covered
 }
 
-fn part2(ranges: &[(u64,u64)]) -> u64 {
-    let mut n = 0;
-    let mut i = 1;
-    let (mut pstart, mut pend) = ranges[0];
-    while i < ranges.len() {
-	match ranges[i] {
-	    (start, end) if start > pend => {
-		// no overlap
-		n += pend - pstart + 1;
-		println!("counted {pstart}-{pend} running total={n}");
-		(pstart, pend) = (start, end);
-	    },
-	    (start, end) if start >= pstart && end <= pend => {
-		// full overlap, skip
-		println!("{start}-{end} fully covered by {pstart}-{pend}");
-	    },
-	    (start, end) if start >= pstart && end >= pend => {
-		// range extended
-		println!("{start}-{end} extends range {pstart}-{pend}");
-		pend = end;
-	    },
-	    (start, end) => {
-		println!("Unprocessed range {start}-{end}, previous {pstart}-{pend}");
-		todo!();
-	    }
-	}
-	i += 1;
-    }
-    // count remaining range
-    n += pend - pstart + 1;
-    println!("counted {pstart}-{pend} running total={n}");
-    n
+/// Disjoint blocks of fresh IDs sorted by start, from ranges in any order that may overlap,
+/// nest or touch. With `coalesce_adjacent`, touching ranges like 3-5 and 6-8 form one block,
+/// otherwise only ranges sharing IDs do.
+fn blocks(ranges: &[Range], coalesce_adjacent: bool) -> Vec<Range> {
+    let fresh = IntervalSet::from_ranges(ranges.iter().copied());
+    if coalesce_adjacent {
+        return fresh.ranges().to_vec();
+    }
+    // IDs in the same range as the ID before them, a block is cut before every other ID
+    let joined = IntervalSet::from_ranges(
+        ranges
+            .iter()
+            .filter(|(start, end)| start < end)
+            .map(|&(start, end)| (start + 1, end)),
+    );
+    let mut blocks = Vec::with_capacity(fresh.ranges().len());
+    for &(start, end) in fresh.ranges() {
+        let mut from = start;
+        if start < end {
+            // each cut is where a range starts, so there are no more of them than ranges
+            for &(first, last) in joined.complement((start + 1, end)).ranges() {
+                for cut in first..=last {
+                    blocks.push((from, cut - 1));
+                    from = cut;
+                }
+            }
+        }
+        blocks.push((from, end));
+    }
+    blocks
+}
+
+/// Number of IDs in disjoint `blocks`, `None` when one is open ended
+fn fresh_count(blocks: impl IntoIterator<Item = Range>) -> Option<u128> {
+    blocks.into_iter().try_fold(0, |n, (start, end)| {
+        (start != Id::MIN && end != Id::MAX).then(|| n + start.abs_diff(end) + 1)
+    })
+}
+
+/// Number of fresh IDs, `None` when a range is open ended
+fn part2(ranges: &[Range], coalesce_adjacent: bool) -> Option<u128> {
+    let blocks = blocks(ranges, coalesce_adjacent);
+    fresh_count(
+        blocks
+            .into_iter()
+            .inspect(|(start, end)| println!("counting {start}-{end}")),
+    )
+}
+
+/// A count of fresh IDs for printing
+fn count(n: Option<u128>) -> String {
+    n.map_or("unbounded".to_string(), |n| n.to_string())
 }
 
 fn main() -> Result<(), Box<dyn Error>> {
-    let filename = env::args()
-        .nth(1)
+    let mut args = env::args().skip(1).peekable();
+    let report = args.next_if(|arg| arg == "report").is_some();
+    let (mut show_cover, mut coalesce_adjacent, mut json) = (false, false, false);
+    let (mut external, mut spill_dir) = (None, None);
+    let mut rest = Vec::new();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("{arg} needs a value"));
+        match arg.as_str() {
+            "--cover" => show_cover = true,
+            "--coalesce-adjacent" => coalesce_adjacent = true,
+            "--json" => json = true,
+            "--external" => external = Some(value()?.parse::<usize>()?),
+            "--spill-dir" => spill_dir = Some(PathBuf::from(value()?)),
+            _ => rest.push(arg),
+        }
+    }
+    // each mode only answers some questions, so reject flags it would ignore
+    let conflicts = [
+        (report, "report", "--cover", show_cover),
+        (report, "report", "--coalesce-adjacent", coalesce_adjacent),
+        (report, "report", "--external", external.is_some()),
+        (external.is_some(), "--external", "--cover", show_cover),
+        (
+            external.is_some(),
+            "--external",
+            "--coalesce-adjacent",
+            coalesce_adjacent,
+        ),
+    ];
+    for (active, mode, flag, set) in conflicts {
+        if active && set {
+            return Err(format!("{flag} can't be used with {mode}").into());
+        }
+    }
+    if json && !report {
+        return Err("--json needs report".into());
+    }
+    if spill_dir.is_some() && external.is_none() {
+        return Err("--spill-dir needs --external".into());
+    }
+    let filename = rest
+        .into_iter()
+        .next()
         .unwrap_or_else(|| "inputs/input.txt".to_string());
+    if let Some(chunk_size) = external {
+        // ranges may not fit in memory, so only part2 is answered
+        let start2 = Instant::now();
+        let spill_dir = spill_dir.unwrap_or_else(env::temp_dir);
+        let n2 = external::count_fresh(&filename, chunk_size.max(1), &spill_dir)?;
+        let duration2 = start2.elapsed();
+        println!("part2: {}, time: {duration2:?}", count(n2));
+        return Ok(());
+    }
     let (ranges, ids) = parse(&filename)?;
+    if report {
+        let report = Report::new(&ranges, &ids);
+        if json {
+            println!("{}", report.to_json());
+        } else {
+            println!("{report}");
+        }
+        return Ok(());
+    }
     let start1 = Instant::now();
     let n1 = part1(&ranges, &ids);
     let duration1 = start1.elapsed();
     println!("part1: {n1}, time: {duration1:?}");
     let start2 = Instant::now();
-    let n2 = part2(&ranges);
+    let n2 = part2(&ranges, coalesce_adjacent);
     let duration2 = start2.elapsed();
-    println!("part2: {n2}, time: {duration2:?}");
+    println!("part2: {}, time: {duration2:?}", count(n2));
+    if show_cover {
+        for (id, by) in cover(&ranges, &ids) {
+            let by = by
+                .iter()
+                .map(|(start, end)| format!("{start}-{end}"))
+                .collect::<Vec<_>>();
+            if by.is_empty() {
+                println!("{id}: spoiled");
+            } else {
+                println!("{id}: fresh in {}", by.join(" "));
+            }
+        }
+    }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sample() {
+        let (ranges, ids) = parse("src/inputs/test1.txt").expect("Parse Error!");
+        assert_eq!(part1(&ranges, &ids), 3);
+        assert_eq!(part2(&ranges, false), Some(14));
+        let covered = cover(&ranges, &ids);
+        assert_eq!(covered[4], (17, vec![(12, 18), (16, 20)]));
+        assert_eq!(
+            covered.iter().filter(|(_, by)| !by.is_empty()).count(),
+            part1(&ranges, &ids)
+        );
+    }
+
+    #[test]
+    fn overlapping() {
+        let cases: [(&[Range], Option<u128>); 10] = [
+            (&[], Some(0)),
+            (&[(3, 5)], Some(3)),
+            // touching, out of order
+            (&[(6, 8), (3, 5)], Some(6)),
+            (&[(3, 10), (4, 5)], Some(8)),
+            (&[(3, 5), (3, 5)], Some(3)),
+            (&[(1, 4), (8, 9), (3, 8)], Some(9)),
+            (&[(0, 0), (2, 2)], Some(2)),
+            // inverted ranges are empty
+            (&[(9, 2), (1, 1)], Some(1)),
+            (&[(-5, -1), (0, 4)], Some(10)),
+            (&[(Id::MIN, 0), (1, 1)], None),
+        ];
+        for (ranges, expected) in cases {
+            for coalesce_adjacent in [false, true] {
+                assert_eq!(part2(ranges, coalesce_adjacent), expected, "{ranges:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn blocks_match_bitmap() {
+        // every list of three ranges within 0-5, inverted ones standing in for shorter lists
+        let all = (0..6).flat_map(|start| (0..6).map(move |end| (start, end)));
+        let all = all.collect::<Vec<Range>>();
+        for &a in &all {
+            for &b in &all {
+                for &c in &all {
+                    let ranges = [a, b, c];
+                    let covers = |id: Id| ranges.iter().any(|&(s, e)| (s..=e).contains(&id));
+                    let bitmap = (0..6).map(covers).collect::<Vec<_>>();
+                    let fresh = bitmap.iter().filter(|&&b| b).count() as u128;
+                    // blocks start after a spoiled ID, or any ID no range shares with the next
+                    let shared = |id: Id| ranges.iter().any(|&(s, e)| s <= id && id < e);
+                    let starts = |coalesce_adjacent: bool| {
+                        (0..6)
+                            .filter(|&id| bitmap[id])
+                            .filter(|&id| {
+                                id == 0
+                                    || !bitmap[id - 1]
+                                    || !(coalesce_adjacent || shared(id as Id - 1))
+                            })
+                            .count()
+                    };
+                    for coalesce_adjacent in [false, true] {
+                        let found = blocks(&ranges, coalesce_adjacent);
+                        assert_eq!(found.len(), starts(coalesce_adjacent), "{ranges:?}");
+                        assert!(found.windows(2).all(|w| w[0].1 < w[1].0), "{found:?}");
+                        let ids = found.iter().flat_map(|&(s, e)| s..=e);
+                        assert!(ids.clone().all(covers), "{ranges:?}");
+                        assert_eq!(ids.count() as u128, fresh, "{ranges:?}");
+                        assert_eq!(part2(&ranges, coalesce_adjacent), Some(fresh));
+                    }
+                }
+            }
+        }
+        assert_eq!(blocks(&[(6, 8), (3, 5), (4, 4)], false), [(3, 5), (6, 8)]);
+        assert_eq!(blocks(&[(6, 8), (3, 5), (9, 2)], true), [(3, 8)]);
+    }
+
+    #[test]
+    fn report() {
+        let (ranges, ids) = parse("src/inputs/test1.txt").expect("Parse Error!");
+        let report = Report::new(&ranges, &ids);
+        assert_eq!((report.fresh, report.blocks), (Some(14), 2));
+        assert_eq!(report.gaps, [(6, 9)]);
+        assert_eq!(
+            (report.max_depth, report.deepest),
+            (2, vec![(12, 14), (16, 18)])
+        );
+        assert_eq!(report.spoiled, [1, 8, 32]);
+    }
+
+    #[test]
+    fn external() {
+        let (ranges, _) = parse("src/inputs/input.txt").expect("Parse Error!");
+        let expected = part2(&ranges, false);
+        for chunk_size in [1, 7, 100, 10000] {
+            let n = external::count_fresh("src/inputs/input.txt", chunk_size, &env::temp_dir())
+                .expect("io error");
+            assert_eq!(n, expected, "chunk_size={chunk_size}");
+        }
+    }
+
+    #[test]
+    fn external_cleans_up() {
+        let dir = env::temp_dir().join(format!("day5-cleanup-{}", std::process::id()));
+        fs::create_dir_all(&dir).expect("io error");
+        let input = dir.join("input.txt");
+        let spills = || fs::read_dir(&dir).expect("io error").count() - 1;
+        // the bad range is only read after two chunks have been spilled
+        fs::write(&input, "1-3\n4-6\n7-x\n").expect("io error");
+        assert!(external::count_fresh(input.to_str().unwrap(), 1, &dir).is_err());
+        assert_eq!(spills(), 0);
+        fs::write(&input, "1-3\n4-6\n\n5\n").expect("io error");
+        let n = external::count_fresh(input.to_str().unwrap(), 1, &dir).expect("io error");
+        assert_eq!((n, spills()), (Some(6), 0));
+        fs::remove_dir_all(&dir).expect("io error");
+    }
+}
@@ -0,0 +1,126 @@
+use crate::{Id, Range, fresh_count};
+use intervals::IntervalSet;
+use std::fmt;
+
+/// Diagnostics for the inventory team: where the fresh ranges leave gaps, where they pile up
+/// and which queried IDs are spoiled
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Report {
+    /// number of fresh IDs, `None` when a range is open ended, and the contiguous blocks they form
+    pub fresh: Option<u128>,
+    pub blocks: usize,
+    /// spoiled stretches between the first and last fresh ID
+    pub gaps: Vec<Range>,
+    /// most ranges covering a single ID, and the stretches where that many overlap
+    pub max_depth: usize,
+    pub deepest: Vec<Range>,
+    /// queried IDs not covered by any range, in input order
+    pub spoiled: Vec<Id>,
+}
+
+impl Report {
+    pub fn new(ranges: &[Range], ids: &[Id]) -> Self {
+        let fresh = IntervalSet::from_ranges(ranges.iter().copied());
+        let gaps = match (fresh.ranges().first(), fresh.ranges().last()) {
+            (Some(first), Some(last)) => fresh.complement((first.0, last.1)).ranges().to_vec(),
+            _ => Vec::new(),
+        };
+        let (max_depth, deepest) = deepest(ranges);
+        Report {
+            fresh: fresh_count(fresh.ranges().iter().copied()),
+            blocks: fresh.ranges().len(),
+            gaps,
+            max_depth,
+            deepest,
+            spoiled: ids
+                .iter()
+                .copied()
+                .filter(|&id| !fresh.contains(id))
+                .collect(),
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        let ranges = |ranges: &[Range]| {
+            ranges
+                .iter()
+                .map(|(start, end)| format!("[{start},{end}]"))
+                .collect::<Vec<_>>()
+                .join(",")
+        };
+        let spoiled = self
+            .spoiled
+            .iter()
+            .map(|id| id.to_string())
+            .collect::<Vec<_>>()
+            .join(",");
+        format!(
+            "{{\"fresh\":{},\"blocks\":{},\"gaps\":[{}],\"max_depth\":{},\"deepest\":[{}],\"spoiled\":[{spoiled}]}}",
+            self.fresh.map_or("null".to_string(), |n| n.to_string()),
+            self.blocks,
+            ranges(&self.gaps),
+            self.max_depth,
+            ranges(&self.deepest),
+        )
+    }
+}
+
+/// Sweep the range boundaries in order, tracking how many ranges cover the IDs in between
+fn deepest(ranges: &[Range]) -> (usize, Vec<Range>) {
+    // ranges start covering at `start` and stop after `end`, an open end never stops
+    let mut events = Vec::with_capacity(ranges.len() * 2);
+    for &(start, end) in ranges.iter().filter(|(start, end)| start <= end) {
+        events.push((start, 1));
+        if end < Id::MAX {
+            events.push((end + 1, -1));
+        }
+    }
+    events.sort_unstable();
+    let (mut depth, mut max_depth) = (0isize, 0);
+    // stretches of constant depth between consecutive boundaries
+    let mut stretches = Vec::new();
+    let mut i = 0;
+    while i < events.len() {
+        let at = events[i].0;
+        while i < events.len() && events[i].0 == at {
+            depth += events[i].1;
+            i += 1;
+        }
+        let until = events.get(i).map_or(Id::MAX, |&(next, _)| next - 1);
+        stretches.push((depth as usize, at, until));
+        max_depth = max_depth.max(depth as usize);
+    }
+    let deepest = IntervalSet::from_ranges(
+        stretches
+            .into_iter()
+            .filter(|&(depth, _, _)| depth == max_depth && max_depth > 0)
+            .map(|(_, start, end)| (start, end)),
+    );
+    (max_depth, deepest.ranges().to_vec())
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "fresh IDs: {} in {} blocks",
+            crate::count(self.fresh),
+            self.blocks
+        )?;
+        writeln!(f, "gaps: {}", self.gaps.len())?;
+        for (start, end) in &self.gaps {
+            writeln!(f, "  {start}-{end} ({} IDs)", start.abs_diff(*end) + 1)?;
+        }
+        let deepest = self
+            .deepest
+            .iter()
+            .map(|(start, end)| format!("{start}-{end}"))
+            .collect::<Vec<_>>();
+        writeln!(f, "max depth: {} at {}", self.max_depth, deepest.join(" "))?;
+        write!(f, "spoiled IDs: {}", self.spoiled.len())?;
+        for id in &self.spoiled {
+            write!(f, "\n  {id}")?;
+        }
+        Ok(())
+    }
+}
@@ -1,132 +1,182 @@
+mod ops;
+mod pretty;
+mod worksheet;
+
+use ops::{Registry, Value};
 use std::env;
 use std::error::Error;
-use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::fs;
+use std::io::{self, IsTerminal};
 use std::time::Instant;
+use worksheet::{Block, Reading};
+
+/// Grand total of a worksheet
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Total {
+    sum: Value,
+    /// problems whose result doesn't fit in `u64`, by index, with their result
+    widened: Vec<(usize, Value)>,
+}
 
-fn parse(filename: &str) -> Result<(Vec<Vec<u64>>, Vec<char>), std::io::Error> {
-    let file = File::open(filename)?;
-    let reader = BufReader::new(file);
-    let (mut numbers, mut ops) = (Vec::new(), Vec::new());
-    let mut max_num = 0;
-    for line in reader.lines() {
-        let line = line?;
-        let cols = line.split_whitespace().collect::<Vec<_>>();
-        if cols[0].as_bytes()[0].is_ascii_digit() {
-            // println!("numbers {cols:?}");
-            let cols = cols
-                .iter()
-                .map(|num| num.parse::<u64>().unwrap())
-                .collect::<Vec<u64>>();
-            let cur_max = cols.iter().max().unwrap();
-            if *cur_max > max_num {
-                max_num = *cur_max;
+/// Sum of every problem's result, with the numbers of each problem read along `reading`.
+/// Every problem that can't be evaluated is reported, rather than just the first.
+fn grand_total(
+    blocks: &[Block],
+    ops: &Registry,
+    reading: Reading,
+) -> Result<Total, Box<dyn Error>> {
+    let mut sum = Value::Wide(0);
+    let mut widened = Vec::new();
+    let mut errors = Vec::new();
+    for (i, block) in blocks.iter().enumerate() {
+        let result = block
+            .numbers(reading)
+            .map_err(|err| err.to_string())
+            .and_then(|numbers| ops.eval(&block.op, &numbers).map_err(|err| err.to_string()));
+        match result {
+            Ok(n) => {
+                if n.exceeds_u64() {
+                    widened.push((i, n.clone()));
+                }
+                sum = sum + n;
             }
-            numbers.push(cols);
-        } else {
-            ops = cols.iter().map(|op| op.chars().next().unwrap()).collect();
-            // println!("ops {cols:?} {ops:?}");
+            Err(err) => errors.push(format!(
+                "problem {} at column {}: {err}",
+                i + 1,
+                block.col + 1
+            )),
         }
     }
-
-    let rows = numbers.len();
-    let cols = numbers[0].len();
-
-    let mut transposed_numbers: Vec<Vec<u64>> = Vec::with_capacity(cols);
-
-    for j in 0..cols {
-        let mut new_row: Vec<u64> = Vec::with_capacity(rows);
-        for i in 0..rows {
-            new_row.push(numbers[i][j]);
-        }
-        transposed_numbers.push(new_row);
+    if errors.is_empty() {
+        Ok(Total { sum, widened })
+    } else {
+        Err(errors.join("\n").into())
     }
-    println!("{max_num}");
-    Ok((transposed_numbers, ops))
 }
 
-fn parse2(filename: &str) -> Result<(Vec<String>, Vec<char>), std::io::Error> {
-    let file = File::open(filename)?;
-    let reader = BufReader::new(file);
-    let mut lines = reader.lines().collect::<Result<Vec<_>, _>>()?;
-    // println!("{lines:?}");
-
-    let ops = lines
-        .pop()
-        .unwrap()
-        .split_whitespace()
-        .map(|op| op.chars().next().unwrap())
-        .collect();
-    let rows = lines.len();
-    let cols = lines[0].len();
-    let mut inverted = Vec::with_capacity(cols);
-    for j in 0..cols {
-        let mut new_row: Vec<u8> = Vec::with_capacity(rows);
-        for i in 0..rows {
-            new_row.push(lines[i].as_bytes()[j]);
-        }
-	// println!("new_row: {}", String::from_utf8(new_row.clone()).unwrap());
-        inverted.push(String::from_utf8(new_row).unwrap());
-    }
-    println!("{inverted:?} {ops:?}");
-
-    Ok((inverted, ops))
+fn part1(blocks: &[Block], ops: &Registry) -> Result<Total, Box<dyn Error>> {
+    grand_total(blocks, ops, Reading::ROWS)
 }
 
-fn part1(numbers: &[Vec<u64>], ops: &[char]) -> u64 {
-    let mut sum = 0;
-    for (i, op) in ops.iter().enumerate() {
-        match op {
-            '*' => sum += numbers[i].iter().product::<u64>(),
-            '+' => sum += numbers[i].iter().sum::<u64>(),
-            _ => panic!("Unexpected op {}", op),
-        }
-    }
-    sum
+fn part2(blocks: &[Block], ops: &Registry) -> Result<Total, Box<dyn Error>> {
+    grand_total(blocks, ops, Reading::COLUMNS)
 }
 
-fn part2(numbers: &[String], ops: &[char]) -> u64 {
-    let mut iter = numbers.iter();
-    let mut grand_total = 0;
-    for op in ops {
-        match op {
-            '*' => {
-		let prod: u64 = iter.by_ref().take_while(|col| !col.trim().is_empty())
-		    .inspect(|n| println!("processing '{n}'"))
-		    .map(|n| n.trim().parse::<u64>().unwrap())
-		    .inspect(|n| println!("processing {n}"))
-		    .product();
-		println!("prod={prod}");
-		grand_total += prod;
-	    },
-	    '+' => {
-		let sum: u64 = iter.by_ref().take_while(|col| !col.trim().is_empty())
-		    .inspect(|n| println!("processing '{n}'"))
-		    .map(|n| n.trim().parse::<u64>().unwrap_or(0))
-		    .inspect(|n| println!("processing {n}"))
-		    .sum();
-		println!("sum={sum}");
-		grand_total += sum
-	    },
-            _ => panic!("Unexpected op {}", op),
-        }
+fn report_widened(blocks: &[Block], total: &Total) {
+    for (i, n) in &total.widened {
+        let width = match n {
+            Value::Wide(_) => "u128",
+            Value::Big(_) => "arbitrary precision",
+        };
+        eprintln!(
+            "problem {} at column {} overflows u64, widened to {width}: {n}",
+            i + 1,
+            blocks[*i].col + 1
+        );
     }
-    grand_total
 }
 
 fn main() -> Result<(), Box<dyn Error>> {
-    let filename = env::args()
-        .nth(1)
+    let mut args = env::args().skip(1);
+    let mut readings = Vec::new();
+    let mut show = false;
+    let mut rest = Vec::new();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("{arg} needs a value"));
+        match arg.as_str() {
+            "--reading" => readings.push(value()?),
+            "--show" => show = true,
+            _ => rest.push(arg),
+        }
+    }
+    let filename = rest
+        .into_iter()
+        .next()
         .unwrap_or_else(|| "inputs/input.txt".to_string());
-    let (numbers, ops) = parse(&filename)?;
+    let input = fs::read_to_string(&filename)?;
+    let blocks = worksheet::parse(&input)?;
+    let ops = Registry::default();
+    if show {
+        let colour = io::stdout().is_terminal();
+        print!(
+            "{}",
+            pretty::table(&blocks, &ops, [Reading::ROWS, Reading::COLUMNS], colour)
+        );
+    }
     let start1 = Instant::now();
-    let n1 = part1(&numbers, &ops);
+    let n1 = part1(&blocks, &ops)?;
     let duration1 = start1.elapsed();
-    println!("part1: {n1}, time: {duration1:?}");
+    report_widened(&blocks, &n1);
+    println!("part1: {}, time: {duration1:?}", n1.sum);
     let start2 = Instant::now();
-    let (numbers, ops) = parse2(&filename)?;
-    let n2 = part2(&numbers, &ops);
+    let n2 = part2(&blocks, &ops)?;
     let duration2 = start2.elapsed();
-    println!("part2: {n2}, time: {duration2:?}");
+    report_widened(&blocks, &n2);
+    println!("part2: {}, time: {duration2:?}", n2.sum);
+    for spec in readings {
+        let total = grand_total(&blocks, &ops, spec.parse()?)?;
+        report_widened(&blocks, &total);
+        println!("{spec}: {}", total.sum);
+    }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sample() {
+        let input = fs::read_to_string("src/inputs/test1.txt").expect("io error");
+        let blocks = worksheet::parse(&input).expect("Parse Error!");
+        let ops = Registry::default();
+        let total = part1(&blocks, &ops).expect("overflow");
+        assert_eq!((total.sum, total.widened), (Value::Wide(4277556), vec![]));
+        assert_eq!(
+            part2(&blocks, &ops).expect("overflow").sum,
+            Value::Wide(3263827)
+        );
+    }
+
+    #[test]
+    fn overflow() {
+        let input = "99999999999 2  1\n99999999999 3  5\n*           /  -\n";
+        let blocks = worksheet::parse(input).expect("Parse Error!");
+        let err = part1(&blocks, &Registry::default()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "problem 3 at column 16: 1 - 5 overflows or is undefined"
+        );
+        // a tall column of 5 digit numbers and a 30 digit number read down a column
+        let mut input = "99999 1\n".repeat(30);
+        input.push_str("*     +\n");
+        let blocks = worksheet::parse(&input).expect("Parse Error!");
+        let ops = Registry::default();
+        let total = part1(&blocks, &ops).expect("overflow");
+        let product = num_bigint::BigUint::from(99999u32).pow(30);
+        assert_eq!(total.sum, Value::Big(product.clone() + 30u32));
+        assert_eq!(total.widened, vec![(0, Value::Big(product))]);
+        // five 30 digit nines multiplied, and thirty ones read as a single number
+        let total = part2(&blocks, &ops).expect("overflow");
+        let ones = "1".repeat(30).parse::<u128>().unwrap();
+        assert_eq!(total.widened[1], (1, Value::Wide(ones)));
+        assert!(matches!(total.widened[0], (0, Value::Big(_))));
+    }
+
+    #[test]
+    fn tall() {
+        // 45 rows, so each column reads as a 45 digit number, too large for u128
+        let mut input = "12 9\n".repeat(45);
+        input.push_str("*  +\n");
+        let blocks = worksheet::parse(&input).expect("Parse Error!");
+        let ops = Registry::default();
+        let total = part2(&blocks, &ops).expect("overflow");
+        let column = |digit: &str| digit.repeat(45).parse::<num_bigint::BigUint>().unwrap();
+        let product = column("1") * column("2");
+        assert_eq!(total.sum, Value::Big(product.clone() + column("9")));
+        assert_eq!(
+            total.widened,
+            vec![(0, Value::Big(product)), (1, Value::Big(column("9")))]
+        );
+    }
+}
@@ -0,0 +1,311 @@
+use num_bigint::BigUint;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::num::{IntErrorKind, ParseIntError};
+use std::ops::Add;
+use std::str::FromStr;
+
+/// Binary operation folded over a problem's numbers, `None` on overflow or an undefined result
+pub type Fold = Box<dyn Fn(u128, u128) -> Option<u128>>;
+/// Arbitrary precision version of a fold, used once the `u128` fold overflows
+pub type BigFold = Box<dyn Fn(&BigUint, &BigUint) -> Option<BigUint>>;
+
+/// Result of a problem or a grand total, `u128` when it fits
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    Wide(u128),
+    Big(BigUint),
+}
+
+impl Value {
+    fn big(&self) -> BigUint {
+        match self {
+            Value::Wide(n) => BigUint::from(*n),
+            Value::Big(n) => n.clone(),
+        }
+    }
+
+    /// Whether the value would have overflowed `u64`
+    pub fn exceeds_u64(&self) -> bool {
+        !matches!(self, Value::Wide(n) if *n <= u64::MAX as u128)
+    }
+}
+
+impl Add for Value {
+    type Output = Value;
+
+    /// Sum, widened to arbitrary precision when it doesn't fit in `u128`
+    fn add(self, other: Value) -> Value {
+        match (&self, &other) {
+            (Value::Wide(a), Value::Wide(b)) if a.checked_add(*b).is_some() => Value::Wide(a + b),
+            _ => Value::Big(self.big() + other.big()),
+        }
+    }
+}
+
+impl From<BigUint> for Value {
+    fn from(n: BigUint) -> Self {
+        match u128::try_from(&n) {
+            Ok(n) => Value::Wide(n),
+            Err(_) => Value::Big(n),
+        }
+    }
+}
+
+impl FromStr for Value {
+    type Err = ParseIntError;
+
+    /// Decimal digits, in arbitrary precision when they don't fit in `u128`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.parse() {
+            Ok(n) => Ok(Value::Wide(n)),
+            Err(err)
+                if *err.kind() == IntErrorKind::PosOverflow
+                    && s.bytes().all(|b| b.is_ascii_digit()) =>
+            {
+                Ok(Value::Big(s.parse().expect("only digits")))
+            }
+            Err(err) => Err(err),
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Wide(n) => write!(f, "{n}"),
+            Value::Big(n) => write!(f, "{n}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EvalError {
+    UnknownOp(String),
+    NoNumbers,
+    /// `lhs op rhs` overflowed or is undefined, like a division by zero
+    Failed {
+        lhs: Value,
+        op: String,
+        rhs: Value,
+    },
+}
+
+impl fmt::Display for EvalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EvalError::UnknownOp(op) => write!(f, "unknown operator '{op}'"),
+            EvalError::NoNumbers => write!(f, "no numbers"),
+            EvalError::Failed { lhs, op, rhs } => {
+                write!(f, "{lhs} {op} {rhs} overflows or is undefined")
+            }
+        }
+    }
+}
+
+impl Error for EvalError {}
+
+struct Op {
+    fold: Fold,
+    big: Option<BigFold>,
+}
+
+/// Operators by the name written on the worksheet
+pub struct Registry {
+    ops: HashMap<String, Op>,
+}
+
+/// Largest result `^` builds in arbitrary precision, about 315,000 digits
+const MAX_POW_BITS: u64 = 1 << 20;
+
+impl Default for Registry {
+    /// `+`, `*`, `-`, `/`, `min`, `max` and `^` (exponentiation). Only `+`, `*` and `^` can
+    /// overflow, they carry on in arbitrary precision, as `min` and `max` do for numbers too
+    /// large for `u128`. `^` fails rather than build a result of more than [`MAX_POW_BITS`] bits.
+    fn default() -> Self {
+        let mut registry = Registry::new();
+        registry.register_big("+", u128::checked_add, |a, b| Some(a + b));
+        registry.register_big("*", u128::checked_mul, |a, b| Some(a * b));
+        registry.register("-", u128::checked_sub);
+        registry.register("/", u128::checked_div);
+        registry.register_big("min", |a, b| Some(a.min(b)), |a, b| Some(a.min(b).clone()));
+        registry.register_big("max", |a, b| Some(a.max(b)), |a, b| Some(a.max(b).clone()));
+        registry.register_big(
+            "^",
+            |a, b| a.checked_pow(b.try_into().ok()?),
+            |a, b| {
+                let b = u32::try_from(b).ok()?;
+                // 0 and 1 stay small, other results need up to b bits per bit of a
+                let small = *a <= BigUint::from(1u32)
+                    || a.bits()
+                        .checked_mul(u64::from(b))
+                        .is_some_and(|bits| bits <= MAX_POW_BITS);
+                small.then(|| a.pow(b))
+            },
+        );
+        registry
+    }
+}
+
+impl Registry {
+    /// Registry without any operators
+    pub fn new() -> Self {
+        Registry {
+            ops: HashMap::new(),
+        }
+    }
+
+    /// Add or replace an operator, overflowing `u128` is an error
+    pub fn register(&mut self, name: &str, fold: impl Fn(u128, u128) -> Option<u128> + 'static) {
+        let op = Op {
+            fold: Box::new(fold),
+            big: None,
+        };
+        self.ops.insert(name.to_string(), op);
+    }
+
+    /// Add or replace an operator that carries on with `big` when `fold` fails
+    pub fn register_big(
+        &mut self,
+        name: &str,
+        fold: impl Fn(u128, u128) -> Option<u128> + 'static,
+        big: impl Fn(&BigUint, &BigUint) -> Option<BigUint> + 'static,
+    ) {
+        let op = Op {
+            fold: Box::new(fold),
+            big: Some(Box::new(big)),
+        };
+        self.ops.insert(name.to_string(), op);
+    }
+
+    /// Fold `op` over `numbers` from the left, in `u128` and then arbitrary precision if the
+    /// operator supports it, from the first result or number that doesn't fit
+    pub fn eval(&self, op: &str, numbers: &[Value]) -> Result<Value, EvalError> {
+        let Op { fold, big } = self
+            .ops
+            .get(op)
+            .ok_or_else(|| EvalError::UnknownOp(op.to_string()))?;
+        let (first, rest) = numbers.split_first().ok_or(EvalError::NoNumbers)?;
+        let failed = |lhs, rhs: &Value| EvalError::Failed {
+            lhs,
+            op: op.to_string(),
+            rhs: rhs.clone(),
+        };
+        let mut lhs = first.clone();
+        for (i, rhs) in rest.iter().enumerate() {
+            if let (&Value::Wide(a), &Value::Wide(b)) = (&lhs, rhs)
+                && let Some(n) = fold(a, b)
+            {
+                lhs = Value::Wide(n);
+                continue;
+            }
+            let Some(big) = big else {
+                return Err(failed(lhs, rhs));
+            };
+            let mut lhs = lhs.big();
+            for rhs in &rest[i..] {
+                lhs = big(&lhs, &rhs.big()).ok_or_else(|| failed(Value::from(lhs.clone()), rhs))?;
+            }
+            return Ok(Value::from(lhs));
+        }
+        Ok(lhs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wide(numbers: &[u128]) -> Vec<Value> {
+        numbers.iter().map(|&n| Value::Wide(n)).collect()
+    }
+
+    #[test]
+    fn builtins() {
+        let ops = Registry::default();
+        assert_eq!(ops.eval("-", &wide(&[100, 30, 7])), Ok(Value::Wide(63)));
+        assert_eq!(ops.eval("/", &wide(&[100, 5, 3])), Ok(Value::Wide(6)));
+        assert_eq!(ops.eval("^", &wide(&[2, 3, 2])), Ok(Value::Wide(64)));
+        assert_eq!(ops.eval("min", &wide(&[4, 2, 9])), Ok(Value::Wide(2)));
+        assert_eq!(ops.eval("max", &wide(&[4, 2, 9])), Ok(Value::Wide(9)));
+        assert_eq!(
+            ops.eval("-", &wide(&[1, 2])),
+            Err(EvalError::Failed {
+                lhs: Value::Wide(1),
+                op: "-".to_string(),
+                rhs: Value::Wide(2)
+            })
+        );
+        assert!(ops.eval("/", &wide(&[1, 0])).is_err());
+        assert_eq!(ops.eval("+", &wide(&[])), Err(EvalError::NoNumbers));
+        assert_eq!(
+            ops.eval("%", &wide(&[1])),
+            Err(EvalError::UnknownOp("%".to_string()))
+        );
+    }
+
+    #[test]
+    fn widening() {
+        let ops = Registry::default();
+        let n = ops.eval("*", &wide(&[u64::MAX as u128, 2])).unwrap();
+        assert_eq!(n, Value::Wide(u64::MAX as u128 * 2));
+        assert!(n.exceeds_u64());
+        // 99999^10 needs 167 bits
+        let big = ops.eval("*", &wide(&[99999; 10])).unwrap();
+        assert_eq!(big, Value::Big(BigUint::from(99999u32).pow(10)));
+        assert_eq!(
+            ops.eval("^", &wide(&[10, 40])).unwrap().to_string(),
+            format!("1{}", "0".repeat(40))
+        );
+        assert_eq!(
+            Value::Wide(u128::MAX) + Value::Wide(1),
+            Value::Big(BigUint::from(u128::MAX) + 1u32)
+        );
+        // numbers too large for u128 are read, and folded, in arbitrary precision
+        let forty = format!("1{}", "0".repeat(40)).parse::<Value>().unwrap();
+        assert_eq!(forty, ops.eval("^", &wide(&[10, 40])).unwrap());
+        assert_eq!("12".parse(), Ok(Value::Wide(12)));
+        assert!("1".repeat(40).parse::<Value>().unwrap().exceeds_u64());
+        assert!(format!("{}x", "9".repeat(40)).parse::<Value>().is_err());
+        assert_eq!(
+            ops.eval("+", &[Value::Wide(1), forty.clone()]).unwrap(),
+            Value::Big(forty.big() + 1u32)
+        );
+        assert_eq!(
+            ops.eval("/", &[forty.clone(), Value::Wide(10)]),
+            Err(EvalError::Failed {
+                lhs: forty.clone(),
+                op: "/".to_string(),
+                rhs: Value::Wide(10)
+            })
+        );
+        let max = ops.eval("max", &[Value::Wide(7), forty.clone()]);
+        assert_eq!(max, Ok(forty.clone()));
+        assert_eq!(
+            ops.eval("min", &[forty, Value::Wide(7)]),
+            Ok(Value::Wide(7))
+        );
+        // results too large to build are errors, unless the base keeps them small
+        assert!(ops.eval("^", &wide(&[99, 4_000_000_000])).is_err());
+        assert!(ops.eval("^", &wide(&[2, 1 << 20])).is_err());
+        assert!(ops.eval("^", &wide(&[2, 1 << 19])).is_ok());
+        assert_eq!(
+            ops.eval("^", &wide(&[1, 4_000_000_000])),
+            Ok(Value::Wide(1))
+        );
+    }
+
+    #[test]
+    fn user_defined() {
+        let mut ops = Registry::new();
+        ops.register("gcd", |mut a, mut b| {
+            while b != 0 {
+                (a, b) = (b, a % b);
+            }
+            Some(a)
+        });
+        assert_eq!(ops.eval("gcd", &wide(&[84, 36, 27])), Ok(Value::Wide(3)));
+        assert!(ops.eval("gcd", &wide(&[])).is_err());
+    }
+}
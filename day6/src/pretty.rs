@@ -0,0 +1,107 @@
+use crate::ops::{Registry, Value};
+use crate::worksheet::{Block, Reading};
+use std::fmt::Write;
+
+/// yellow, for problems whose readings give different results
+const HIGHLIGHT: &str = "\x1b[33m";
+const RESET: &str = "\x1b[0m";
+
+/// Numbers of a problem read along `reading` and its result
+fn evaluate(
+    block: &Block,
+    ops: &Registry,
+    reading: Reading,
+) -> (Vec<Value>, Result<Value, String>) {
+    match block.numbers(reading) {
+        Ok(numbers) => {
+            let result = ops.eval(&block.op, &numbers).map_err(|err| err.to_string());
+            (numbers, result)
+        }
+        Err(err) => (Vec::new(), Err(err.to_string())),
+    }
+}
+
+/// Evaluated problem as an equation, e.g. `123 * 45 * 6 = 33210`
+fn equation(op: &str, (numbers, result): &(Vec<Value>, Result<Value, String>)) -> String {
+    let terms = numbers
+        .iter()
+        .map(|n| n.to_string())
+        .collect::<Vec<_>>()
+        .join(&format!(" {op} "));
+    match result {
+        Ok(n) => format!("{terms} = {n}"),
+        Err(err) => format!("{terms} = error: {err}"),
+    }
+}
+
+/// Every problem as an equation in both readings side by side. Problems whose results differ
+/// are marked with `*`, and also coloured when `colour` is set.
+pub fn table(blocks: &[Block], ops: &Registry, readings: [Reading; 2], colour: bool) -> String {
+    let rows = blocks
+        .iter()
+        .map(|block| {
+            let [left, right] = readings.map(|reading| evaluate(block, ops, reading));
+            let differs = left.1 != right.1;
+            let [left, right] = [left, right].map(|side| equation(&block.op, &side));
+            (block, left, right, differs)
+        })
+        .collect::<Vec<_>>();
+    let headings = readings.map(|reading| reading.to_string());
+    let width = rows
+        .iter()
+        .map(|(_, left, _, _)| left.len())
+        .chain([headings[0].len()])
+        .max()
+        .unwrap_or(0);
+    let mut out = String::new();
+    writeln!(
+        out,
+        "  {:>4} {:>6}  {:width$}  {}",
+        "#", "column", headings[0], headings[1]
+    )
+    .unwrap();
+    for (i, (block, left, right, differs)) in rows.iter().enumerate() {
+        let line = format!(
+            "{} {:>4} {:>6}  {left:width$}  {right}",
+            if *differs { '*' } else { ' ' },
+            i + 1,
+            block.col + 1
+        );
+        if *differs && colour {
+            writeln!(out, "{HIGHLIGHT}{line}{RESET}").unwrap();
+        } else {
+            writeln!(out, "{line}").unwrap();
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::worksheet;
+
+    #[test]
+    fn sample() {
+        let blocks = worksheet::parse("123 12\n 45 21\n  6   \n*   + \n").expect("Parse Error!");
+        let ops = Registry::default();
+        assert_eq!(
+            equation("*", &evaluate(&blocks[0], &ops, Reading::ROWS)),
+            "123 * 45 * 6 = 33210"
+        );
+        assert_eq!(
+            table(&blocks, &ops, [Reading::ROWS, Reading::COLUMNS], false),
+            "     # column  rows                  columns\n\
+             *    1      1  123 * 45 * 6 = 33210  1 * 24 * 356 = 8544\n\
+             \x20    2      5  12 + 21 = 33          12 + 21 = 33\n"
+        );
+        let coloured = table(&blocks, &ops, [Reading::ROWS, Reading::COLUMNS], true);
+        assert_eq!(
+            coloured
+                .lines()
+                .filter(|line| line.starts_with(HIGHLIGHT))
+                .count(),
+            1
+        );
+    }
+}
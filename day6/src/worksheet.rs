@@ -0,0 +1,258 @@
+use crate::ops::Value;
+use std::error::Error;
+use std::fmt;
+use std::num::ParseIntError;
+use std::str::FromStr;
+
+/// One problem of the worksheet: the columns between two all-blank separator columns
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Block {
+    /// first column of the block in the worksheet
+    pub col: usize,
+    /// number rows of the block, padded with spaces to the block width
+    pub rows: Vec<Vec<u8>>,
+    /// operator from the last line, e.g. "*"
+    pub op: String,
+}
+
+/// Lines of cells a block is read along, each line is one number
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Axis {
+    Rows,
+    Columns,
+    /// down-right diagonals, the one through the bottom left corner first
+    Diagonals,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Forward,
+    Backward,
+}
+
+/// How the numbers of a problem are read from its block
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Reading {
+    pub axis: Axis,
+    /// order the lines are taken in: top to bottom, left to right, or backwards
+    pub order: Direction,
+    /// order of the digits along a line, most significant first, or backwards
+    pub digits: Direction,
+}
+
+impl Reading {
+    /// Numbers across each row, as humans read them
+    pub const ROWS: Reading = Reading {
+        axis: Axis::Rows,
+        order: Direction::Forward,
+        digits: Direction::Forward,
+    };
+
+    /// Numbers down each column, cephalopod style
+    pub const COLUMNS: Reading = Reading {
+        axis: Axis::Columns,
+        order: Direction::Forward,
+        digits: Direction::Forward,
+    };
+}
+
+impl FromStr for Reading {
+    type Err = String;
+
+    /// An axis, `rows`, `columns` or `diagonals`, optionally followed by `,reverse` to take the
+    /// lines backwards and `,reverse-digits` to read each line backwards
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut words = s.split(',');
+        let axis = match words.next() {
+            Some("rows") => Axis::Rows,
+            Some("columns") => Axis::Columns,
+            Some("diagonals") => Axis::Diagonals,
+            _ => {
+                return Err(format!(
+                    "unknown reading '{s}', expected rows, columns or diagonals"
+                ));
+            }
+        };
+        let mut reading = Reading {
+            axis,
+            order: Direction::Forward,
+            digits: Direction::Forward,
+        };
+        for word in words {
+            match word {
+                "reverse" => reading.order = Direction::Backward,
+                "reverse-digits" => reading.digits = Direction::Backward,
+                _ => return Err(format!("unknown reading option '{word}' in '{s}'")),
+            }
+        }
+        Ok(reading)
+    }
+}
+
+impl fmt::Display for Reading {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let axis = match self.axis {
+            Axis::Rows => "rows",
+            Axis::Columns => "columns",
+            Axis::Diagonals => "diagonals",
+        };
+        write!(f, "{axis}")?;
+        if self.order == Direction::Backward {
+            write!(f, ",reverse")?;
+        }
+        if self.digits == Direction::Backward {
+            write!(f, ",reverse-digits")?;
+        }
+        Ok(())
+    }
+}
+
+impl Block {
+    pub fn width(&self) -> usize {
+        self.rows.first().map_or(0, |row| row.len())
+    }
+
+    /// Numbers of the problem read along `reading`, in arbitrary precision when they don't fit
+    /// in `u128`. Spaces around a row's number are padding, as are all spaces down columns and
+    /// diagonals, so a row `1 2` is an error while a column with a gap reads as one number.
+    /// Lines without digits are skipped.
+    pub fn numbers(&self, reading: Reading) -> Result<Vec<Value>, ParseIntError> {
+        let (height, width) = (self.rows.len(), self.width());
+        let mut lines: Vec<Vec<u8>> = match reading.axis {
+            Axis::Rows => self.rows.clone(),
+            Axis::Columns => (0..width)
+                .map(|c| self.rows.iter().map(|row| row[c]).collect())
+                .collect(),
+            // on diagonal d the column is r + d - (height - 1)
+            Axis::Diagonals => (0..height + width - 1)
+                .map(|d| {
+                    (0..height)
+                        .filter_map(|r| self.rows[r].get((r + d).checked_sub(height - 1)?))
+                        .copied()
+                        .collect()
+                })
+                .collect(),
+        };
+        if reading.order == Direction::Backward {
+            lines.reverse();
+        }
+        lines
+            .into_iter()
+            .map(|mut line| {
+                if reading.axis == Axis::Rows {
+                    line = line.trim_ascii().to_vec();
+                } else {
+                    line.retain(|&b| b != b' ');
+                }
+                if reading.digits == Direction::Backward {
+                    line.reverse();
+                }
+                line
+            })
+            .filter(|line| !line.is_empty())
+            .map(|line| String::from_utf8_lossy(&line).parse())
+            .collect()
+    }
+}
+
+/// Split a worksheet into problem blocks by its all-blank columns. Lines can be ragged, short
+/// lines are padded with spaces. The last line holds the operators.
+pub fn parse(input: &str) -> Result<Vec<Block>, Box<dyn Error>> {
+    let mut lines = input.lines().map(str::as_bytes).collect::<Vec<_>>();
+    while lines
+        .last()
+        .is_some_and(|line| line.iter().all(|&b| b == b' '))
+    {
+        // trailing blank lines
+        lines.pop();
+    }
+    let ops = lines.pop().ok_or("worksheet is empty")?;
+    let width = lines
+        .iter()
+        .chain([&ops])
+        .map(|line| line.len())
+        .max()
+        .unwrap_or(0);
+    let cell = |line: &[u8], c: usize| line.get(c).copied().unwrap_or(b' ');
+    let blank = |c: usize| lines.iter().chain([&ops]).all(|line| cell(line, c) == b' ');
+    let mut blocks = Vec::new();
+    let mut c = 0;
+    while c < width {
+        if blank(c) {
+            c += 1;
+            continue;
+        }
+        let col = c;
+        while c < width && !blank(c) {
+            c += 1;
+        }
+        let rows = lines
+            .iter()
+            .map(|line| (col..c).map(|c| cell(line, c)).collect())
+            .collect();
+        let op = String::from_utf8_lossy(&(col..c).map(|c| cell(ops, c)).collect::<Vec<_>>())
+            .trim()
+            .to_string();
+        if op.is_empty() {
+            return Err(format!("no operator for the problem at column {}", col + 1).into());
+        }
+        blocks.push(Block { col, rows, op });
+    }
+    Ok(blocks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wide(numbers: Result<Vec<Value>, ParseIntError>) -> Vec<u128> {
+        let wide = |n| match n {
+            Value::Wide(n) => n,
+            Value::Big(n) => panic!("{n} doesn't fit in u128"),
+        };
+        numbers.unwrap().into_iter().map(wide).collect()
+    }
+
+    #[test]
+    fn ragged() {
+        // the first row and the operator line are shorter than the second
+        let input = "12 3\n 5 67\n+  *\n";
+        let blocks = parse(input).expect("Parse Error!");
+        assert_eq!(blocks.len(), 2);
+        assert_eq!((blocks[1].col, blocks[1].op.as_str()), (3, "*"));
+        assert_eq!(wide(blocks[0].numbers(Reading::ROWS)), vec![12, 5]);
+        assert_eq!(wide(blocks[0].numbers(Reading::COLUMNS)), vec![1, 25]);
+        assert_eq!(wide(blocks[1].numbers(Reading::ROWS)), vec![3, 67]);
+        assert_eq!(wide(blocks[1].numbers(Reading::COLUMNS)), vec![36, 7]);
+        assert!(parse("1 2\n+\n").is_err());
+        // a gap inside a row's number is an error, only columns close it up
+        let blocks = parse("1 2\n345\n+  \n").expect("Parse Error!");
+        assert!(blocks[0].numbers(Reading::ROWS).is_err());
+        assert!(
+            blocks[0]
+                .numbers("rows,reverse-digits".parse().unwrap())
+                .is_err()
+        );
+        assert_eq!(wide(blocks[0].numbers(Reading::COLUMNS)), vec![13, 4, 25]);
+    }
+
+    #[test]
+    fn readings() {
+        // 123
+        //  45
+        //   6
+        let blocks = parse("123\n 45\n  6\n*  \n").expect("Parse Error!");
+        let read = |spec: &str| wide(blocks[0].numbers(spec.parse().unwrap()));
+        assert_eq!(read("rows"), vec![123, 45, 6]);
+        assert_eq!(read("rows,reverse"), vec![6, 45, 123]);
+        assert_eq!(read("rows,reverse-digits"), vec![321, 54, 6]);
+        assert_eq!(read("columns"), vec![1, 24, 356]);
+        assert_eq!(read("columns,reverse,reverse-digits"), vec![653, 42, 1]);
+        assert_eq!(read("diagonals"), vec![146, 25, 3]);
+        assert_eq!(read("diagonals,reverse"), vec![3, 25, 146]);
+        let spec = "columns,reverse,reverse-digits";
+        assert_eq!(spec.parse::<Reading>().unwrap().to_string(), spec);
+        assert!("rows,upside-down".parse::<Reading>().is_err());
+        assert!("spiral".parse::<Reading>().is_err());
+    }
+}
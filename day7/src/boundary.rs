@@ -0,0 +1,67 @@
+use std::str::FromStr;
+
+/// What happens to a beam sent past the left or right side of the manifold
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Boundary {
+    /// the beam is absorbed by the side and its timeline ends there
+    #[default]
+    Absorb,
+    /// the side is a mirror, the beam comes back into the outermost column
+    Reflect,
+    /// the beam comes in through the opposite side
+    Wrap,
+}
+
+impl FromStr for Boundary {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "absorb" => Ok(Boundary::Absorb),
+            "reflect" => Ok(Boundary::Reflect),
+            "wrap" => Ok(Boundary::Wrap),
+            _ => Err(format!(
+                "unknown boundary '{s}', expected absorb, reflect or wrap"
+            )),
+        }
+    }
+}
+
+impl Boundary {
+    /// Column a beam ends up in when sent `dc` columns sideways from `col`, `None` when absorbed
+    pub fn column(self, col: usize, dc: isize, width: usize) -> Option<usize> {
+        let target = col as isize + dc;
+        if (0..width as isize).contains(&target) {
+            return Some(target as usize);
+        }
+        let width = width as isize;
+        match self {
+            Boundary::Absorb => None,
+            // -1 comes back to 0 and width to width - 1
+            Boundary::Reflect => Some(if target < 0 {
+                (-target - 1).min(width - 1) as usize
+            } else {
+                (2 * width - 1 - target).max(0) as usize
+            }),
+            Boundary::Wrap => Some(target.rem_euclid(width) as usize),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn columns() {
+        assert_eq!(Boundary::Absorb.column(0, -1, 5), None);
+        assert_eq!(Boundary::Absorb.column(4, 1, 5), None);
+        assert_eq!(Boundary::Absorb.column(3, 1, 5), Some(4));
+        assert_eq!(Boundary::Reflect.column(0, -1, 5), Some(0));
+        assert_eq!(Boundary::Reflect.column(4, 1, 5), Some(4));
+        assert_eq!(Boundary::Reflect.column(0, -1, 1), Some(0));
+        assert_eq!(Boundary::Wrap.column(0, -1, 5), Some(4));
+        assert_eq!(Boundary::Wrap.column(4, 1, 5), Some(0));
+        assert!("bounce".parse::<Boundary>().is_err());
+    }
+}
@@ -0,0 +1,143 @@
+use grid::Grid;
+use std::collections::HashMap;
+
+/// How a kind of cell treats the beams reaching it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Kind {
+    /// rows and columns the beam moves by, one beam each, usually into the next row. No outputs
+    /// absorb the beam, more than one split it.
+    pub outputs: Vec<(isize, isize)>,
+    /// whether the cell also emits a beam of its own
+    pub source: bool,
+}
+
+/// Kinds of cell by the character drawing them
+pub struct Cells {
+    kinds: HashMap<u8, Kind>,
+}
+
+impl Default for Cells {
+    /// `.` empty, `S` source, `^` splitter, `v` three-way splitter, `/` and `\` deflectors
+    /// sending the beam down-left and down-right, `<` and `>` pushing it sideways along its
+    /// row, `#` absorber
+    fn default() -> Self {
+        let mut cells = Cells::new();
+        cells.register(b'.', &[0]);
+        cells.register_source(b'S', &[0]);
+        cells.register(b'^', &[-1, 1]);
+        cells.register(b'v', &[-1, 0, 1]);
+        cells.register(b'/', &[-1]);
+        cells.register(b'\\', &[1]);
+        cells.register_moves(b'<', &[(0, -1)]);
+        cells.register_moves(b'>', &[(0, 1)]);
+        cells.register(b'#', &[]);
+        cells
+    }
+}
+
+impl Cells {
+    /// Registry without any kinds
+    pub fn new() -> Self {
+        Cells {
+            kinds: HashMap::new(),
+        }
+    }
+
+    /// Add or replace a kind of cell sending beams into the next row, `outputs` columns over
+    pub fn register(&mut self, c: u8, outputs: &[isize]) {
+        let outputs = outputs.iter().map(|&dc| (1, dc)).collect::<Vec<_>>();
+        self.register_moves(c, &outputs);
+    }
+
+    /// Add or replace a kind of cell moving beams by any number of rows and columns
+    pub fn register_moves(&mut self, c: u8, outputs: &[(isize, isize)]) {
+        let kind = Kind {
+            outputs: outputs.to_vec(),
+            source: false,
+        };
+        self.kinds.insert(c, kind);
+    }
+
+    /// Add or replace a kind of cell emitting a beam
+    pub fn register_source(&mut self, c: u8, outputs: &[isize]) {
+        let kind = Kind {
+            outputs: outputs.iter().map(|&dc| (1, dc)).collect(),
+            source: true,
+        };
+        self.kinds.insert(c, kind);
+    }
+
+    /// Register a kind written as `c=move,move,...`, a move being a column offset into the next
+    /// row or `dr:dc`, e.g. `w=-2,2` for a wide splitter or `u=-1:0` sending beams up. `c=` is
+    /// an absorber.
+    pub fn register_spec(&mut self, spec: &str) -> Result<(), String> {
+        let invalid = || format!("invalid cell '{spec}', expected e.g. w=-2,2 or u=-1:0");
+        let (c, outputs) = spec.split_once('=').ok_or_else(invalid)?;
+        let &[c] = c.as_bytes() else {
+            return Err(invalid());
+        };
+        let outputs = outputs
+            .split(',')
+            .filter(|output| !output.is_empty())
+            .map(|output| {
+                let (dr, dc) = output.split_once(':').unwrap_or(("1", output));
+                Ok((
+                    dr.trim().parse().map_err(|_| invalid())?,
+                    dc.trim().parse().map_err(|_| invalid())?,
+                ))
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        self.register_moves(c, &outputs);
+        Ok(())
+    }
+
+    /// Check every cell of `grid` is a known kind and, with `downward`, that beams only ever
+    /// move into the next row
+    pub fn check(&self, grid: &Grid<u8>, downward: bool) -> Result<(), String> {
+        // each character only needs checking once
+        let mut checked = [false; 256];
+        for ((r, c), &cell) in grid.iter() {
+            if checked[cell as usize] {
+                continue;
+            }
+            checked[cell as usize] = true;
+            let at = || format!("'{}' at row {} column {}", cell as char, r + 1, c + 1);
+            match self.get(cell) {
+                None => return Err(format!("unknown cell {}", at())),
+                Some(kind) if downward && kind.outputs.iter().any(|&(dr, _)| dr != 1) => {
+                    return Err(format!(
+                        "cell {} moves beams other than down a row, use --graph",
+                        at()
+                    ));
+                }
+                Some(_) => (),
+            }
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, c: u8) -> Option<&Kind> {
+        self.kinds.get(&c)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn specs() {
+        let mut cells = Cells::default();
+        cells.register_spec("w=-2, 2").unwrap();
+        cells.register_spec("x=").unwrap();
+        cells.register_spec("u=-1:0,1").unwrap();
+        assert_eq!(cells.get(b'w').unwrap().outputs, vec![(1, -2), (1, 2)]);
+        assert_eq!(cells.get(b'u').unwrap().outputs, vec![(-1, 0), (1, 1)]);
+        assert_eq!(cells.get(b'x').unwrap().outputs, vec![]);
+        assert!(cells.get(b'S').unwrap().source);
+        assert!(cells.register_spec("w").is_err());
+        assert!(cells.register_spec("ww=1").is_err());
+        assert!(cells.register_spec("w=left").is_err());
+        assert!(cells.register_spec("w=1:").is_err());
+    }
+}
@@ -0,0 +1,290 @@
+use crate::boundary::Boundary;
+use crate::cells::Cells;
+use grid::{Grid, Pos};
+use std::collections::{HashMap, HashSet, VecDeque};
+
+/// The manifold compiled to the cells where timelines start or branch, so beams may move any
+/// way rather than only down a row.
+///
+/// Nodes are the sources and splitters beams reach, plus one cell of every loop a beam can
+/// circle through cells with a single output. Each node has an edge per output, to the next
+/// node its beam reaches or `None` where the timeline ends.
+pub struct Graph {
+    nodes: Vec<Pos>,
+    edges: Vec<Vec<Option<usize>>>,
+    sources: Vec<usize>,
+    splitters: Vec<bool>,
+}
+
+/// Why the timelines of a graph can't be counted
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Uncountable {
+    /// beams can go round these nodes forever, in this order
+    Cycle(Vec<Pos>),
+    /// there are too many for a `u128`
+    Overflow,
+}
+
+impl Graph {
+    pub fn compile(grid: &Grid<u8>, cells: &Cells, boundary: Boundary) -> Result<Self, String> {
+        cells.check(grid, false)?;
+        let kind = |pos: Pos| cells.get(grid[pos]).expect("checked above");
+        let step = |(r, c): Pos, (dr, dc): (isize, isize)| {
+            let r = r.checked_add_signed(dr).filter(|&r| r < grid.height())?;
+            Some((r, boundary.column(c, dc, grid.width())?))
+        };
+        let mut graph = Graph {
+            nodes: Vec::new(),
+            edges: Vec::new(),
+            sources: Vec::new(),
+            splitters: Vec::new(),
+        };
+        let mut index = HashMap::new();
+        let mut queue = Vec::new();
+        for pos in grid.positions().filter(|&pos| kind(pos).source) {
+            let splitter = kind(pos).outputs.len() > 1;
+            let i = graph.node(&mut index, &mut queue, pos, splitter);
+            graph.sources.push(i);
+        }
+        while let Some(i) = queue.pop() {
+            let from = graph.nodes[i];
+            let mut edges = Vec::new();
+            for &output in &kind(from).outputs {
+                // follow the beam through cells with a single output
+                let mut seen = HashSet::new();
+                let mut at = step(from, output);
+                let edge = loop {
+                    let Some(pos) = at else { break None };
+                    let here = kind(pos);
+                    if index.contains_key(&pos)
+                        || here.source
+                        || here.outputs.len() > 1
+                        || !seen.insert(pos)
+                    {
+                        let splitter = here.outputs.len() > 1;
+                        break Some(graph.node(&mut index, &mut queue, pos, splitter));
+                    }
+                    match here.outputs[..] {
+                        [output] => at = step(pos, output),
+                        _ => break None,
+                    }
+                };
+                edges.push(edge);
+            }
+            graph.edges[i] = edges;
+        }
+        Ok(graph)
+    }
+
+    /// Index of the node at `pos`, a new node is queued to have its edges followed
+    fn node(
+        &mut self,
+        index: &mut HashMap<Pos, usize>,
+        queue: &mut Vec<usize>,
+        pos: Pos,
+        splitter: bool,
+    ) -> usize {
+        *index.entry(pos).or_insert_with(|| {
+            self.nodes.push(pos);
+            self.edges.push(Vec::new());
+            self.splitters.push(splitter);
+            queue.push(self.nodes.len() - 1);
+            self.nodes.len() - 1
+        })
+    }
+
+    /// Number of splitters any beam reaches
+    pub fn splits(&self) -> usize {
+        self.splitters.iter().filter(|&&splitter| splitter).count()
+    }
+
+    /// A cycle of nodes, in the order the beam goes around it
+    pub fn find_cycle(&self) -> Option<Vec<Pos>> {
+        // 0 unvisited, 1 on the current path, 2 done
+        let mut state = vec![0u8; self.nodes.len()];
+        for root in 0..self.nodes.len() {
+            if state[root] != 0 {
+                continue;
+            }
+            let mut path = vec![(root, 0)];
+            state[root] = 1;
+            while let Some(&mut (i, ref mut next)) = path.last_mut() {
+                let Some(&edge) = self.edges[i].get(*next) else {
+                    state[i] = 2;
+                    path.pop();
+                    continue;
+                };
+                *next += 1;
+                match edge.map(|j| (j, state[j])) {
+                    Some((j, 0)) => {
+                        state[j] = 1;
+                        path.push((j, 0));
+                    }
+                    Some((j, 1)) => {
+                        let start = path.iter().position(|&(k, _)| k == j).unwrap();
+                        return Some(path[start..].iter().map(|&(k, _)| self.nodes[k]).collect());
+                    }
+                    _ => (),
+                }
+            }
+        }
+        None
+    }
+
+    /// Nodes so every edge goes forward, or a cycle
+    fn topological_order(&self) -> Result<Vec<usize>, Uncountable> {
+        let mut incoming = vec![0; self.nodes.len()];
+        for &j in self.edges.iter().flatten().flatten() {
+            incoming[j] += 1;
+        }
+        let mut ready = (0..self.nodes.len())
+            .filter(|&i| incoming[i] == 0)
+            .collect::<VecDeque<_>>();
+        let mut order = Vec::with_capacity(self.nodes.len());
+        while let Some(i) = ready.pop_front() {
+            order.push(i);
+            for &j in self.edges[i].iter().flatten() {
+                incoming[j] -= 1;
+                if incoming[j] == 0 {
+                    ready.push_back(j);
+                }
+            }
+        }
+        if order.len() == self.nodes.len() {
+            Ok(order)
+        } else {
+            let cycle = self.find_cycle().expect("nodes left over are on a cycle");
+            Err(Uncountable::Cycle(cycle))
+        }
+    }
+
+    /// Number of timelines from a beam at each node, and in total
+    fn timelines_from(&self, order: &[usize]) -> Result<(Vec<u128>, u128), Uncountable> {
+        let mut from = vec![0u128; self.nodes.len()];
+        for &i in order.iter().rev() {
+            from[i] = if self.edges[i].is_empty() {
+                1
+            } else {
+                self.edges[i]
+                    .iter()
+                    .map(|edge| edge.map_or(1, |j| from[j]))
+                    .try_fold(0, crate::add)
+                    .map_err(|_| Uncountable::Overflow)?
+            };
+        }
+        let total = self
+            .sources
+            .iter()
+            .map(|&i| from[i])
+            .try_fold(0, crate::add)
+            .map_err(|_| Uncountable::Overflow)?;
+        Ok((from, total))
+    }
+
+    /// Number of timelines, unless beams can go round forever or there are too many
+    pub fn count_timelines(&self) -> Result<u128, Uncountable> {
+        let (_, total) = self.timelines_from(&self.topological_order()?)?;
+        Ok(total)
+    }
+
+    /// Splitters reachable from the node at `pos`, `None` when it isn't a node
+    pub fn reachable(&self, pos: Pos) -> Option<Vec<Pos>> {
+        let start = self.nodes.iter().position(|&node| node == pos)?;
+        let mut seen = vec![false; self.nodes.len()];
+        let mut stack = vec![start];
+        while let Some(i) = stack.pop() {
+            for &j in self.edges[i].iter().flatten() {
+                if !seen[j] {
+                    seen[j] = true;
+                    stack.push(j);
+                }
+            }
+        }
+        let mut reachable = (0..self.nodes.len())
+            .filter(|&i| seen[i] && self.splitters[i])
+            .map(|i| self.nodes[i])
+            .collect::<Vec<_>>();
+        reachable.sort_unstable();
+        Some(reachable)
+    }
+
+    /// Splitters every timeline passes, those with as many timelines through them as there are
+    /// in total, unless the timelines can't be counted
+    pub fn unavoidable(&self) -> Result<Vec<Pos>, Uncountable> {
+        let order = self.topological_order()?;
+        let (from, total) = self.timelines_from(&order)?;
+        // timelines reaching each node, every source starting one
+        let mut into = vec![0u128; self.nodes.len()];
+        for &i in &self.sources {
+            into[i] = crate::add(into[i], 1).map_err(|_| Uncountable::Overflow)?;
+        }
+        for &i in &order {
+            for &j in self.edges[i].iter().flatten() {
+                into[j] = crate::add(into[j], into[i]).map_err(|_| Uncountable::Overflow)?;
+            }
+        }
+        // a product too large for a u128 is more than the total
+        let mut unavoidable = (0..self.nodes.len())
+            .filter(|&i| self.splitters[i] && into[i].checked_mul(from[i]) == Some(total))
+            .map(|i| self.nodes[i])
+            .collect::<Vec<_>>();
+        unavoidable.sort_unstable();
+        Ok(unavoidable)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn compile(input: &str) -> Graph {
+        let grid = crate::parse_grid(input).expect("Parse Error!");
+        Graph::compile(&grid, &Cells::default(), Boundary::Absorb).unwrap()
+    }
+
+    #[test]
+    fn sample() {
+        let input = std::fs::read_to_string("src/inputs/test1.txt").expect("io error");
+        let graph = compile(&input);
+        assert_eq!(graph.splits(), 21);
+        assert_eq!(graph.count_timelines(), Ok(40));
+        assert_eq!(graph.find_cycle(), None);
+        assert_eq!(graph.unavoidable(), Ok(vec![(2, 7)]));
+        assert_eq!(graph.reachable((12, 12)), Some(vec![(14, 13)]));
+        assert_eq!(graph.reachable((1, 1)), None);
+    }
+
+    #[test]
+    fn sideways() {
+        // pushed along the second row into a splitter, whose beams split again
+        let graph = compile("S....\n>>^..\n.^.^.\n.....\n");
+        assert_eq!(graph.splits(), 3);
+        assert_eq!(graph.count_timelines(), Ok(4));
+        assert_eq!(graph.unavoidable(), Ok(vec![(1, 2)]));
+        // trapped between two pushers
+        let graph = compile("S.\n><\n");
+        assert_eq!(graph.find_cycle(), Some(vec![(1, 0)]));
+        assert_eq!(
+            graph.count_timelines(),
+            Err(Uncountable::Cycle(vec![(1, 0)]))
+        );
+        // a cycle through a splitter sending one beam back up
+        let mut cells = Cells::default();
+        cells.register_spec("u=-1:1,1:0").unwrap();
+        let grid = crate::parse_grid("S.\nu<\n").expect("Parse Error!");
+        let graph = Graph::compile(&grid, &cells, Boundary::Absorb).unwrap();
+        assert!(graph.find_cycle().is_some());
+    }
+
+    #[test]
+    fn overflow() {
+        let compile = |rows| {
+            let grid = crate::manifold(".S.", rows);
+            Graph::compile(&grid, &Cells::default(), Boundary::Wrap).unwrap()
+        };
+        assert_eq!(compile(80).unavoidable(), Ok(vec![(1, 1)]));
+        let graph = compile(81);
+        assert_eq!(graph.count_timelines(), Err(Uncountable::Overflow));
+        assert_eq!(graph.unavoidable(), Err(Uncountable::Overflow));
+    }
+}
@@ -1,105 +1,366 @@
+mod boundary;
+mod cells;
+mod graph;
+mod render;
+mod timelines;
+
+use boundary::Boundary;
+use cells::Cells;
+use graph::{Graph, Uncountable};
+use grid::Grid;
+use grid::Pos;
 use std::env;
-use std::collections::HashMap;
 use std::error::Error;
-use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
 use std::time::Instant;
+use timelines::{SplitMix64, Timelines};
 
-fn parse(filename: &str) -> Result<Vec<Vec<u8>>, std::io::Error> {
-    let file = File::open(filename)?;
-    let reader = BufReader::new(file);
-    let grid = reader
-        .lines()
-	.map(|line| line.expect("io error").as_bytes().to_vec())
-        .collect::<Vec<_>>();
-    // println!("grid={grid:?}");
+/// Error for a manifold with more timelines than a `u128` can count
+const OVERFLOW: &str = "too many timelines to count in a u128";
+
+fn parse(filename: &str) -> Result<Grid<u8>, Box<dyn Error>> {
+    let input = fs::read_to_string(filename)?;
+    let grid = parse_grid(&input)?;
+    // println!("grid:\n{}", grid.render(|&c| c as char));
     Ok(grid)
 }
 
+/// Manifold with one byte per cell, every kind of cell being drawn with an ASCII character
+fn parse_grid(input: &str) -> Result<Grid<u8>, Box<dyn Error>> {
+    let grid = Grid::parse(input, |c| c)?;
+    if let Some(((r, c), cell)) = grid.iter().find(|(_, cell)| !cell.is_ascii()) {
+        return Err(format!("unknown cell '{cell}' at row {} column {}", r + 1, c + 1).into());
+    }
+    Ok(grid.map(|&c| c as u8))
+}
+
+/// Beams sent down the manifold: how many splitters they hit and how many timelines there are
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Sweep {
+    splits: usize,
+    timelines: u128,
+}
+
+/// Propagate the number of timelines in each column row by row. Every source starts a
+/// timeline and each cell sends the timelines reaching it to its outputs in the next row. A
+/// cell with several outputs hit by any beam counts as one split, what happens at the sides is
+/// up to `boundary`. A timeline ends below the bottom row, or earlier when its beam is absorbed
+/// by a side or a cell without outputs. Too many timelines to count is an error.
+fn sweep(grid: &Grid<u8>, cells: &Cells, boundary: Boundary) -> Result<Sweep, String> {
+    trace(grid, cells, boundary, |_| ())
+}
+
+/// Number of timelines passing through each cell
+fn heatmap(grid: &Grid<u8>, cells: &Cells, boundary: Boundary) -> Result<Grid<u128>, String> {
+    let mut through = Vec::with_capacity(grid.width() * grid.height());
+    trace(grid, cells, boundary, |row| through.extend_from_slice(row))?;
+    Ok(Grid::from_vec(grid.width(), grid.height(), through))
+}
+
+/// The sweep, passing `on_row` the number of timelines through each cell of every row
+fn trace(
+    grid: &Grid<u8>,
+    cells: &Cells,
+    boundary: Boundary,
+    mut on_row: impl FnMut(&[u128]),
+) -> Result<Sweep, String> {
+    cells.check(grid, true)?;
+    // kinds looked up once per character rather than once per cell
+    let kinds = (0..=u8::MAX).map(|c| cells.get(c)).collect::<Vec<_>>();
+    let width = grid.width();
+    let mut counts = vec![0u128; width];
+    let (mut splits, mut ended) = (0, 0);
+    for row in grid.rows() {
+        for (c, &cell) in row.iter().enumerate() {
+            if kinds[cell as usize].expect("checked above").source {
+                counts[c] += 1;
+            }
+        }
+        on_row(&counts);
+        let mut next = vec![0u128; width];
+        for (c, &n) in counts.iter().enumerate().filter(|&(_, &n)| n > 0) {
+            let kind = kinds[row[c] as usize].expect("checked above");
+            if kind.outputs.len() > 1 {
+                splits += 1;
+            }
+            if kind.outputs.is_empty() {
+                ended = add(ended, n)?;
+            }
+            for &(_, dc) in &kind.outputs {
+                match boundary.column(c, dc, width) {
+                    Some(c) => next[c] = add(next[c], n)?,
+                    None => ended = add(ended, n)?,
+                }
+            }
+        }
+        counts = next;
+    }
+    Ok(Sweep {
+        splits,
+        timelines: counts.into_iter().try_fold(ended, add)?,
+    })
+}
 
-fn part1(grid: &[Vec<u8>]) -> usize {
-    let start_col = grid[0].iter().position(|&c| c == b'S').expect("Start not found!");
-    let mut beams = grid[0].clone(); // beams tracks all current beams
-    beams[start_col] = b'|';
-    let mut splits = 0;
-    for r in 1..grid.len() {
-	let ncols = grid[r].len();
-	let row = &grid[r];
-	println!("row {r} {}", String::from_utf8(row.to_vec()).unwrap());
-	let mut new_beams = vec![b'.'; ncols];
-	for c in 0..ncols {
-	    match (beams[c], row[c]) {
-		(b'|', b'.')  => { // beam can continue
-		    // println!("Beam continues at {r},{c}");
-		    new_beams[c] = b'|';
-		},
-		(b'|', b'^') => {	// beam splits
-		    // println!("Beam splits at {r},{c}");
-		    splits += 1;
-		    if c != 0 {	// split left
-			new_beams[c-1] = b'|';
-		    }
-		    if c < ncols - 1 { // split right
-			new_beams[c+1] = b'|';
-		    }
-		},
-		(_, _) => (),
-	    }
-	}
-	beams = new_beams;
-    }
-    splits
+/// Add up timelines, an error when there are too many to count
+fn add(a: u128, b: u128) -> Result<u128, &'static str> {
+    a.checked_add(b).ok_or(OVERFLOW)
 }
 
-fn tachyon_timelines(seen: &mut HashMap<(usize, usize), usize>, row: usize, grid: &[Vec<u8>], col: usize) -> usize {
-    if row == grid.len() - 1 {
-	// timeline ends
-	return 1;
-    }
-    if let Some(timelines) = seen.get(&(row, col)) {
-	println!("Seen row {row},{col} = {timelines}");
-	return *timelines;
-    } 
-    println!("processing row {row},{col}");
-    let timelines = match grid[row][col] {
-	b'.' => {
-	    // println!("Beam continues at {row},{col}");
-	    tachyon_timelines(seen, row + 1, grid, col)
-	},
-	b'^' => {
-	    // println!("Beam split at {row},{col}");
-	    // this assumes no beam splitter at corners
-	    tachyon_timelines(seen, row + 1, grid, col - 1) +
-		tachyon_timelines(seen, row + 1, grid, col + 1)
-	}
-	_ => {
-	    panic!("Shouldn't reach here!");
-	}
-    };
-    seen.insert((row, col), timelines);
-    timelines
+/// Position written as `r,c`
+fn parse_pos(s: &str) -> Result<Pos, String> {
+    let invalid = || format!("invalid position '{s}', expected r,c");
+    let (r, c) = s.split_once(',').ok_or_else(invalid)?;
+    Ok((
+        r.trim().parse().map_err(|_| invalid())?,
+        c.trim().parse().map_err(|_| invalid())?,
+    ))
 }
 
-fn part2(grid: &[Vec<u8>]) -> usize {
-    let col = grid[0].iter().position(|&c| c == b'S').expect("Start not found!");
-    let mut n = 0;
-    let mut seen = HashMap::new();
-    n = tachyon_timelines(&mut seen, 1, grid, col);
-    n
+fn positions(positions: &[Pos]) -> String {
+    let positions = positions
+        .iter()
+        .map(|(r, c)| format!("{r},{c}"))
+        .collect::<Vec<_>>();
+    positions.join(" ")
+}
+
+/// Answer both parts on the compiled graph, so beams may move any way
+fn run_graph(
+    grid: &Grid<u8>,
+    cells: &Cells,
+    boundary: Boundary,
+    reachable: &[Pos],
+) -> Result<(), Box<dyn Error>> {
+    let start = Instant::now();
+    let graph = Graph::compile(grid, cells, boundary)?;
+    println!("part1: {}", graph.splits());
+    match graph.count_timelines() {
+        Ok(timelines) => println!("part2: {timelines}, time: {:?}", start.elapsed()),
+        Err(Uncountable::Cycle(cycle)) => println!(
+            "part2: unbounded, beams cycle through {}",
+            positions(&cycle)
+        ),
+        Err(Uncountable::Overflow) => return Err(OVERFLOW.into()),
+    }
+    if let Ok(unavoidable) = graph.unavoidable() {
+        println!("on every timeline: {}", positions(&unavoidable));
+    }
+    for &pos in reachable {
+        match graph.reachable(pos) {
+            Some(splitters) => println!(
+                "reachable from {},{}: {}",
+                pos.0,
+                pos.1,
+                positions(&splitters)
+            ),
+            None => println!("no splitter or source at {},{}", pos.0, pos.1),
+        }
+    }
+    Ok(())
 }
 
 fn main() -> Result<(), Box<dyn Error>> {
-    let filename = env::args()
-        .nth(1)
+    let mut args = env::args().skip(1);
+    let mut boundary = Boundary::default();
+    let mut cells = Cells::default();
+    let (mut overlay, mut heatmap_file, mut scale) = (false, None, 4);
+    let (mut first, mut kth, mut samples, mut seed) = (0, None, 0, 0);
+    let (mut use_graph, mut reachable) = (false, Vec::new());
+    let mut rest = Vec::new();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("{arg} needs a value"));
+        match arg.as_str() {
+            "--boundary" => boundary = value()?.parse()?,
+            "--cell" => cells.register_spec(&value()?)?,
+            "--overlay" => overlay = true,
+            "--heatmap" => heatmap_file = Some(PathBuf::from(value()?)),
+            "--scale" => scale = value()?.parse()?,
+            "--timelines" => first = value()?.parse()?,
+            "--timeline" => kth = Some(value()?.parse()?),
+            "--sample" => samples = value()?.parse()?,
+            "--seed" => seed = value()?.parse()?,
+            "--graph" => use_graph = true,
+            "--reachable" => reachable.push(parse_pos(&value()?)?),
+            _ => rest.push(arg),
+        }
+    }
+    let row_by_row = overlay || heatmap_file.is_some() || first > 0 || kth.is_some() || samples > 0;
+    if use_graph && row_by_row {
+        return Err(
+            "--overlay, --heatmap, --timelines, --timeline and --sample can't be used with --graph"
+                .into(),
+        );
+    }
+    if !use_graph && !reachable.is_empty() {
+        return Err("--reachable needs --graph".into());
+    }
+    let filename = rest
+        .into_iter()
+        .next()
         .unwrap_or_else(|| "inputs/test1.txt".to_string());
     let grid = parse(&filename)?;
-    let start1 = Instant::now();
-    let n1 = part1(&grid);
-    let duration1 = start1.elapsed();
-    println!("part1: {n1}, time: {duration1:?}");
-    let start2 = Instant::now();
-    let n2 = part2(&grid);
-    let duration2 = start2.elapsed();
-    println!("part2: {n2}, time: {duration2:?}");
+    if use_graph {
+        return run_graph(&grid, &cells, boundary, &reachable);
+    }
+    if overlay || heatmap_file.is_some() {
+        let heat = heatmap(&grid, &cells, boundary)?;
+        if overlay {
+            print!("{}", render::overlay(&grid, &heat));
+        }
+        if let Some(path) = heatmap_file {
+            let mut out = BufWriter::new(File::create(&path)?);
+            if path.extension().is_some_and(|ext| ext == "csv") {
+                render::write_csv(&mut out, &heat)?;
+            } else {
+                render::write_ppm(&mut out, &grid, &heat, scale)?;
+            }
+            out.flush()?;
+            println!("wrote heatmap to {}", path.display());
+        }
+    }
+    if first > 0 || kth.is_some() || samples > 0 {
+        let timelines = Timelines::new(&grid, &cells, boundary)?;
+        for (k, timeline) in timelines.iter().take(first).enumerate() {
+            println!("timeline {k}: {timeline}");
+        }
+        if let Some(k) = kth {
+            match timelines.kth(k) {
+                Some(timeline) => println!("timeline {k}: {timeline}"),
+                None => println!("timeline {k}: only {} timelines", timelines.count()),
+            }
+        }
+        let mut rng = SplitMix64::new(seed);
+        for _ in 0..samples {
+            if let Some(timeline) = timelines.sample(&mut rng) {
+                println!("sample: {timeline}");
+            }
+        }
+    }
+    // both parts come out of the same sweep
+    let start = Instant::now();
+    let Sweep { splits, timelines } = sweep(&grid, &cells, boundary)?;
+    let duration = start.elapsed();
+    println!("part1: {splits}");
+    println!("part2: {timelines}, time: {duration:?}");
     Ok(())
 }
+
+/// Three-way splitters wrapping round below `sources`, so every row triples the timelines
+#[cfg(test)]
+fn manifold(sources: &str, rows: usize) -> Grid<u8> {
+    let input = format!("{sources}\n{}", "vvv\n".repeat(rows));
+    parse_grid(&input).expect("Parse Error!")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sample() {
+        let grid = parse("src/inputs/test1.txt").expect("Parse Error!");
+        assert_eq!(
+            sweep(&grid, &Cells::default(), Boundary::Absorb).unwrap(),
+            Sweep {
+                splits: 21,
+                timelines: 40
+            }
+        );
+    }
+
+    #[test]
+    fn tall() {
+        // far deeper than recursing once per row would allow
+        let mut input = String::from(".S.\n");
+        input.push_str(&"...\n.^.\n".repeat(100_000));
+        let grid = parse_grid(&input).expect("Parse Error!");
+        assert_eq!(
+            sweep(&grid, &Cells::default(), Boundary::Absorb).unwrap(),
+            Sweep {
+                splits: 1,
+                timelines: 2
+            }
+        );
+    }
+
+    #[test]
+    fn edge_splitters() {
+        let sweeps = |input: &str| {
+            let grid = parse_grid(input).expect("Parse Error!");
+            [Boundary::Absorb, Boundary::Reflect, Boundary::Wrap]
+                .map(|boundary| sweep(&grid, &Cells::default(), boundary).unwrap())
+                .map(|sweep| (sweep.splits, sweep.timelines))
+        };
+        // splitters in the first column
+        assert_eq!(sweeps("S...\n^...\n....\n^^..\n"), [(2, 3), (3, 4), (2, 3)]);
+        // and in the last one
+        assert_eq!(sweeps("...S\n...^\n^.^.\n...^\n"), [(3, 4), (3, 5), (4, 6)]);
+    }
+
+    #[test]
+    fn elements() {
+        let sweep = |input: &str| {
+            let grid = parse_grid(input).expect("Parse Error!");
+            let Sweep { splits, timelines } =
+                sweep(&grid, &Cells::default(), Boundary::Absorb).unwrap();
+            (splits, timelines)
+        };
+        // three-way splitter, then one beam absorbed and the others deflected
+        assert_eq!(sweep("..S..\n..v..\n.#/\\.\n"), (1, 3));
+        // three sources, the one in column 0 also passing on the first one's beam
+        assert_eq!(sweep("S....\nS..S.\n^....\n"), (1, 5));
+        let grid = parse_grid("S\n?\n").expect("Parse Error!");
+        assert_eq!(
+            super::sweep(&grid, &Cells::default(), Boundary::Absorb),
+            Err("unknown cell '?' at row 2 column 1".to_string())
+        );
+        // not read as '^', the low byte of 'Ş'
+        assert_eq!(
+            parse_grid(".S.\n.Ş.\n...\n").unwrap_err().to_string(),
+            "unknown cell 'Ş' at row 2 column 2"
+        );
+        let grid = parse_grid("S.\n><\n").expect("Parse Error!");
+        assert_eq!(
+            super::sweep(&grid, &Cells::default(), Boundary::Absorb),
+            Err(
+                "cell '>' at row 2 column 1 moves beams other than down a row, use --graph"
+                    .to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn overflow() {
+        let cells = Cells::default();
+        let fits = sweep(&manifold(".S.", 80), &cells, Boundary::Wrap).unwrap();
+        assert_eq!(fits.timelines, 3u128.pow(80));
+        let grid = manifold(".S.", 81);
+        assert_eq!(
+            sweep(&grid, &cells, Boundary::Wrap),
+            Err(OVERFLOW.to_string())
+        );
+        assert_eq!(
+            heatmap(&grid, &cells, Boundary::Wrap),
+            Err(OVERFLOW.to_string())
+        );
+    }
+
+    #[test]
+    fn heat() {
+        let grid = parse_grid("..S..\n.....\n..^..\n.^...\n").expect("Parse Error!");
+        let heat = heatmap(&grid, &Cells::default(), Boundary::Absorb).unwrap();
+        assert_eq!(heat.row(3), &[0, 1, 0, 1, 0]);
+        assert_eq!(
+            render::overlay(&grid, &heat),
+            "..S..\n..|..\n..^..\n.^.|.\n"
+        );
+        let mut csv = Vec::new();
+        render::write_csv(&mut csv, &heat).unwrap();
+        assert_eq!(csv, b"0,0,1,0,0\n0,0,1,0,0\n0,0,1,0,0\n0,1,0,1,0\n");
+        let mut ppm = Vec::new();
+        render::write_ppm(&mut ppm, &grid, &heat, 2).unwrap();
+        assert_eq!(ppm.len(), "P6\n10 8\n255\n".len() + 10 * 8 * 3);
+    }
+}
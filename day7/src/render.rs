@@ -0,0 +1,64 @@
+use grid::Grid;
+use std::io::{self, Write};
+
+const EMPTY: [u8; 3] = [0, 0, 0];
+/// any cell other than `.` that no beam reaches
+const CELL: [u8; 3] = [90, 90, 90];
+
+/// The manifold with `|` trails on the empty cells beams pass through
+pub fn overlay(grid: &Grid<u8>, heat: &Grid<u128>) -> String {
+    let mut out = String::with_capacity((grid.width() + 1) * grid.height());
+    for (row, heat) in grid.rows().zip(heat.rows()) {
+        for (&cell, &n) in row.iter().zip(heat) {
+            out.push(if cell == b'.' && n > 0 {
+                '|'
+            } else {
+                cell as char
+            });
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// Timelines through each cell, one line of comma separated counts per row
+pub fn write_csv(out: &mut impl Write, heat: &Grid<u128>) -> io::Result<()> {
+    for row in heat.rows() {
+        let line = row.iter().map(|n| n.to_string()).collect::<Vec<_>>();
+        writeln!(out, "{}", line.join(","))?;
+    }
+    Ok(())
+}
+
+/// Colour for `n` timelines out of at most `max`, on a log scale from dark red through yellow
+/// to white
+fn heat_colour(n: u128, max: u128) -> [u8; 3] {
+    let t = if max > 1 {
+        (n as f64).ln() / (max as f64).ln()
+    } else {
+        1.0
+    };
+    let h = t * 3.0;
+    let x = ((h % 1.0) * 255.0) as u8;
+    match h as usize {
+        0 => [64 + x / 4 * 3, 0, 0],
+        1 => [255, x, 0],
+        2 => [255, 255, x],
+        _ => [255, 255, 255],
+    }
+}
+
+/// Heatmap of the timelines through each cell as a binary PPM image, `scale` pixels per cell
+pub fn write_ppm(
+    out: &mut impl Write,
+    grid: &Grid<u8>,
+    heat: &Grid<u128>,
+    scale: usize,
+) -> io::Result<()> {
+    let max = heat.iter().map(|(_, &n)| n).max().unwrap_or(0);
+    heat.write_ppm(out, scale, |pos, &n| match (grid[pos], n) {
+        (b'.', 0) => EMPTY,
+        (_, 0) => CELL,
+        _ => heat_colour(n, max),
+    })
+}
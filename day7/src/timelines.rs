@@ -0,0 +1,224 @@
+use crate::boundary::Boundary;
+use crate::cells::{Cells, Kind};
+use grid::{Grid, Pos};
+use std::fmt;
+
+/// One timeline: the source its beam comes from and the way it goes at every cell with more
+/// than one output, as the column offset taken
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Timeline {
+    pub source: Pos,
+    pub choices: Vec<(Pos, isize)>,
+}
+
+impl fmt::Display for Timeline {
+    /// Source then one letter per choice, `L`eft, `S`traight on or `R`ight
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{},{} ", self.source.0, self.source.1)?;
+        for &(_, dc) in &self.choices {
+            let choice = match dc {
+                ..0 => 'L',
+                0 => 'S',
+                1.. => 'R',
+            };
+            write!(f, "{choice}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Every timeline of a manifold, ordered by source in reading order and then
+/// lexicographically by the output taken at each choice, in the order the kind lists them
+pub struct Timelines<'a> {
+    grid: &'a Grid<u8>,
+    cells: &'a Cells,
+    boundary: Boundary,
+    /// number of timelines from a beam entering each cell, counted from the bottom row up
+    ways: Grid<u128>,
+    sources: Vec<Pos>,
+    count: u128,
+}
+
+impl<'a> Timelines<'a> {
+    /// Count the timelines from every cell, an error when there are too many for a `u128`
+    pub fn new(grid: &'a Grid<u8>, cells: &'a Cells, boundary: Boundary) -> Result<Self, String> {
+        cells.check(grid, true)?;
+        let mut timelines = Timelines {
+            grid,
+            cells,
+            boundary,
+            ways: Grid::new(grid.width(), grid.height(), 0),
+            sources: Vec::new(),
+            count: 0,
+        };
+        for r in (0..grid.height()).rev() {
+            for c in 0..grid.width() {
+                let kind = timelines.kind((r, c));
+                let ways = if kind.outputs.is_empty() {
+                    1
+                } else {
+                    kind.outputs
+                        .iter()
+                        .map(|&(_, dc)| timelines.weight((r, c), dc))
+                        .try_fold(0, crate::add)?
+                };
+                timelines.ways[(r, c)] = ways;
+                if kind.source {
+                    timelines.sources.push((r, c));
+                }
+            }
+        }
+        timelines.sources.sort_unstable();
+        timelines.count = timelines
+            .sources
+            .iter()
+            .map(|&source| timelines.ways[source])
+            .try_fold(0, crate::add)?;
+        Ok(timelines)
+    }
+
+    fn kind(&self, pos: Pos) -> &'a Kind {
+        self.cells.get(self.grid[pos]).expect("checked in new")
+    }
+
+    /// Cell a beam leaving `(r, c)` by `dc` enters, `None` when its timeline ends
+    fn next(&self, (r, c): Pos, dc: isize) -> Option<Pos> {
+        let c = self.boundary.column(c, dc, self.grid.width())?;
+        (r + 1 < self.grid.height()).then_some((r + 1, c))
+    }
+
+    /// Number of timelines leaving `pos` by `dc`
+    fn weight(&self, pos: Pos, dc: isize) -> u128 {
+        self.next(pos, dc).map_or(1, |next| self.ways[next])
+    }
+
+    pub fn count(&self) -> u128 {
+        self.count
+    }
+
+    /// The `k`th timeline, counting from 0. Each choice skips the timelines of the outputs
+    /// before the one taken, so this follows a single beam.
+    pub fn kth(&self, mut k: u128) -> Option<Timeline> {
+        let mut sources = self.sources.iter();
+        let source = *sources.find(|&&source| {
+            let found = k < self.ways[source];
+            if !found {
+                k -= self.ways[source];
+            }
+            found
+        })?;
+        let mut choices = Vec::new();
+        let mut pos = Some(source);
+        while let Some(here) = pos {
+            let outputs = &self.kind(here).outputs;
+            pos = None;
+            for &(_, dc) in outputs {
+                let weight = self.weight(here, dc);
+                if k < weight {
+                    if outputs.len() > 1 {
+                        choices.push((here, dc));
+                    }
+                    pos = self.next(here, dc);
+                    break;
+                }
+                k -= weight;
+            }
+        }
+        Some(Timeline { source, choices })
+    }
+
+    /// Timelines in order, each found only when needed
+    pub fn iter(&self) -> impl Iterator<Item = Timeline> + '_ {
+        (0..self.count()).map(|k| self.kth(k).expect("k is below the count"))
+    }
+
+    /// A timeline chosen uniformly at random
+    pub fn sample(&self, rng: &mut SplitMix64) -> Option<Timeline> {
+        let n = self.count();
+        (n > 0).then(|| self.kth(rng.below(n)).expect("k is below the count"))
+    }
+}
+
+/// Small seeded generator, so samples can be reproduced
+pub struct SplitMix64(u64);
+
+impl SplitMix64 {
+    pub fn new(seed: u64) -> Self {
+        SplitMix64(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in `0..n`, the lowest `2^128 % n` values are rejected so none is more likely
+    pub fn below(&mut self, n: u128) -> u128 {
+        let threshold = n.wrapping_neg() % n;
+        loop {
+            let x = (u128::from(self.next_u64()) << 64) | u128::from(self.next_u64());
+            if x >= threshold {
+                return x % n;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn sample() {
+        let input = std::fs::read_to_string("src/inputs/test1.txt").expect("io error");
+        let grid = crate::parse_grid(&input).expect("Parse Error!");
+        let cells = Cells::default();
+        let timelines = Timelines::new(&grid, &cells, Boundary::Absorb).unwrap();
+        assert_eq!(timelines.count(), 40);
+        let all = timelines.iter().collect::<Vec<_>>();
+        assert_eq!(all.len(), 40);
+        assert_eq!(all[0].to_string(), "0,7 LLLLLLL");
+        assert_eq!(all[39].to_string(), "0,7 RRRRRRR");
+        // strictly increasing, so all different
+        let choices = |t: &Timeline| t.choices.iter().map(|&(_, dc)| dc).collect::<Vec<_>>();
+        assert!(all.windows(2).all(|w| choices(&w[0]) < choices(&w[1])));
+        assert_eq!(timelines.kth(40), None);
+        let mut rng = SplitMix64::new(7);
+        let seen = (0..4000)
+            .map(|_| timelines.sample(&mut rng).unwrap().to_string())
+            .collect::<HashSet<_>>();
+        assert_eq!(seen.len(), 40);
+    }
+
+    #[test]
+    fn sources_and_edges() {
+        let grid = crate::parse_grid("S..S\n^.v.\n...^\n").expect("Parse Error!");
+        let cells = Cells::default();
+        for boundary in [Boundary::Absorb, Boundary::Reflect, Boundary::Wrap] {
+            let timelines = Timelines::new(&grid, &cells, boundary).unwrap();
+            let sweep = crate::sweep(&grid, &cells, boundary).unwrap();
+            assert_eq!(timelines.count(), sweep.timelines);
+            assert_eq!(timelines.iter().count() as u128, sweep.timelines);
+        }
+    }
+
+    #[test]
+    fn overflow() {
+        let cells = Cells::default();
+        let count =
+            |grid: &Grid<u8>| Timelines::new(grid, &cells, Boundary::Wrap).map(|t| t.count());
+        let grid = crate::manifold(".S.", 80);
+        let timelines = Timelines::new(&grid, &cells, Boundary::Wrap).unwrap();
+        let last = timelines.kth(3u128.pow(80) - 1).unwrap();
+        assert_eq!(last.to_string(), format!("0,1 {}", "R".repeat(80)));
+        // each source alone fits, but not all three together
+        assert_eq!(
+            count(&crate::manifold("SSS", 80)),
+            Err(crate::OVERFLOW.to_string())
+        );
+    }
+}
@@ -1,8 +1,13 @@
-use std::collections::HashSet;
+mod spanning_tree;
+mod union_find;
+
+use spanning_tree::minimum_spanning_tree;
 use std::env;
 use std::error::Error;
-use std::fs;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
 use std::time::Instant;
+use union_find::UnionFind;
 
 #[derive(Debug, Clone, Copy)]
 struct Point3D(i64, i64, i64);
@@ -51,105 +56,36 @@ fn get_distances_sq(points: &[Point3D]) -> Vec<PairwiseDistance> {
 fn part1(points: &[Point3D], num_connections: usize) -> usize {
     let mut distances_sq = get_distances_sq(points);
     distances_sq.sort();
-    let mut circuits: Vec<HashSet<usize>> = Vec::new();
-    for dist in &distances_sq[..num_connections] {
-        // println!("Processing {dist:?} {:?}-{:?})", points[dist.1], points[dist.2]);
-        let mut join_idx = None;
-        let mut other_point = None;
-        for (i, c) in circuits.iter_mut().enumerate() {
-            // println!("Checking circuit: {c:?}");
-            if c.contains(&dist.1) {
-                other_point = Some(dist.2);
-            } else if c.contains(&dist.2) {
-                other_point = Some(dist.1);
-            }
-            if let Some(p) = other_point {
-                // println!("Found circuit: {c:?} with {p:?}");
-                c.insert(p);
-                join_idx = Some(i);
-                break;
-            }
-        }
-        if let Some(p) = other_point {
-            let join_idx = join_idx.unwrap();
-            for j in (join_idx + 1)..circuits.len() {
-                if circuits[j].contains(&p) {
-                    let otherc = circuits.remove(j);
-                    // println!("Found duplicate circuit at {j}: {:?}", otherc);
-                    circuits[join_idx].extend(otherc);
-                    break;
-                }
-            }
-        } else {
-            // new circuit
-            let mut new_circuit = HashSet::new();
-            new_circuit.insert(dist.1);
-            new_circuit.insert(dist.2);
-            // println!("New circuit: {new_circuit:?} {:?}-{:?})", points[dist.1], points[dist.2]);
-            circuits.push(new_circuit);
-        }
+    let mut circuits = UnionFind::new(points.len());
+    for &PairwiseDistance(_, i, j) in distances_sq.iter().take(num_connections) {
+        circuits.union(i, j);
     }
-    circuits.sort_by_key(|k| std::cmp::Reverse(k.len()));
-    // println!("{:?}", &circuits[..3]);
-    circuits[..3].iter().map(|c| c.len()).product()
+    circuits.sizes().iter().take(3).product()
 }
 
 fn part2(points: &[Point3D]) -> u64 {
-    let mut distances_sq = get_distances_sq(points);
-    distances_sq.sort();
-    let mut circuits: Vec<HashSet<usize>> = Vec::new();
-    let mut processed_points = HashSet::new();
-    let mut answer = 0;
-    for dist in &distances_sq {
-        processed_points.insert(dist.1);
-        processed_points.insert(dist.2);
-        let mut join_idx = None;
-        let mut other_point = None;
-        for (i, c) in circuits.iter_mut().enumerate() {
-            // println!("Checking circuit: {c:?}");
-            if c.contains(&dist.1) {
-                other_point = Some(dist.2);
-            } else if c.contains(&dist.2) {
-                other_point = Some(dist.1);
-            }
-            if let Some(p) = other_point {
-                c.insert(p);
-                // println!("Added {p:?} to circuit {i}: {c:?}");
-                join_idx = Some(i);
-                break;
-            }
-        }
-        if let Some(p) = other_point {
-            let join_idx = join_idx.unwrap();
-            for j in (join_idx + 1)..circuits.len() {
-                if circuits[j].contains(&p) {
-                    let otherc = circuits.remove(j);
-                    // println!("Found duplicate circuit at {j}: {:?}", otherc);
-                    circuits[join_idx].extend(otherc);
-                    // println!("Extended circuit at {join_idx}: {:?}", circuits[join_idx]);
-                    break;
-                }
-            }
-        } else {
-            // new circuit
-            let mut new_circuit = HashSet::new();
-            new_circuit.insert(dist.1);
-            new_circuit.insert(dist.2);
-            // println!("New circuit {}: {new_circuit:?} {:?}-{:?})", circuits.len(), points[dist.1], points[dist.2]);
-            circuits.push(new_circuit);
-        }
-        // println!("Processing {dist:?} {:?}-{:?}, circuits: {})", points[dist.1], points[dist.2], circuits.len());
-        if processed_points.len() == points.len() && circuits.len() == 1 {
-            answer = points[dist.1].0 * points[dist.2].0;
-            break;
-        }
-    }
-    answer as u64
+    // the connection that joined the last two circuits
+    minimum_spanning_tree(points)
+        .edges
+        .last()
+        .map_or(0, |edge| (points[edge.a].0 * points[edge.b].0) as u64)
 }
 
 fn main() -> Result<(), Box<dyn Error + 'static>> {
-    let filename = env::args()
-        .nth(1)
+    let mut args = env::args().skip(1);
+    let (mut tree_file, mut merges) = (None, Vec::new());
+    let mut rest = Vec::new();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("{arg} needs a value"));
+        match arg.as_str() {
+            "--tree" => tree_file = Some(value()?),
+            "--merge" => merges.push(value()?.parse::<usize>()?),
+            _ => rest.push(arg),
+        }
+    }
+    let filename = rest
+        .into_iter()
+        .next()
         .unwrap_or_else(|| "inputs/input.txt".to_string());
     let input = fs::read_to_string(&filename)?;
     let points = parse(&input)?;
@@ -161,6 +97,33 @@ fn main() -> Result<(), Box<dyn Error + 'static>> {
     let n2 = part2(&points);
     let duration2 = start2.elapsed();
     println!("part2: {n2}, time: {duration2:?}");
+    if tree_file.is_some() || !merges.is_empty() {
+        let tree = minimum_spanning_tree(&points);
+        println!(
+            "spanning tree: {} merges, total length {:.3}",
+            tree.edges.len(),
+            tree.total_length
+        );
+        // merges count only the connections that joined two circuits, unlike part1
+        for k in merges {
+            match tree.edges.get(k) {
+                Some(edge) => println!(
+                    "merge {k}: {:?} - {:?}, length {:.3}, circuit of {}",
+                    points[edge.a],
+                    points[edge.b],
+                    edge.length(),
+                    edge.size
+                ),
+                None => println!("merge {k}: only {} merges", tree.edges.len()),
+            }
+        }
+        if let Some(path) = tree_file {
+            let mut out = BufWriter::new(File::create(&path)?);
+            tree.write_csv(&mut out, &points)?;
+            out.flush()?;
+            println!("wrote spanning tree to {path}");
+        }
+    }
     Ok(())
 }
 
@@ -204,4 +167,26 @@ mod tests {
         assert!(n2 == 25272);
         println!("part2: {n2}, time: {duration2:?}");
     }
+
+    #[test]
+    fn spanning_tree() {
+        let input = "0,0,0\n3,4,0\n0,0,1\n10,0,0\n3,4,2\n";
+        let points = parse(input).expect("Parse Error!");
+        let tree = minimum_spanning_tree(&points);
+        let edges = tree
+            .edges
+            .iter()
+            .map(|edge| (edge.a, edge.b, edge.dist_sq, edge.size))
+            .collect::<Vec<_>>();
+        // 0-2 and 1-4 pair up, 0-1 (25) joins the pairs ahead of 1-2 (26), 1-3 adds the last box
+        assert_eq!(
+            edges,
+            vec![(0, 2, 1, 2), (1, 4, 4, 2), (0, 1, 25, 4), (1, 3, 65, 5)]
+        );
+        assert!((tree.total_length - (1.0 + 2.0 + 5.0 + 65f64.sqrt())).abs() < 1e-9);
+        let mut csv = Vec::new();
+        tree.write_csv(&mut csv, &points).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        assert_eq!(csv.lines().nth(1), Some("0,0,2,0,0,0,0,0,1,1,1.000,2"));
+    }
 }
@@ -0,0 +1,74 @@
+use crate::union_find::UnionFind;
+use crate::{PairwiseDistance, Point3D, get_distances_sq};
+use std::io::{self, Write};
+
+/// Connection between two junction boxes that joined two circuits
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Edge {
+    pub a: usize,
+    pub b: usize,
+    pub dist_sq: u64,
+    /// number of boxes in the circuit the connection made
+    pub size: usize,
+}
+
+impl Edge {
+    pub fn length(&self) -> f64 {
+        (self.dist_sq as f64).sqrt()
+    }
+}
+
+/// Minimum spanning tree of the junction boxes
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpanningTree {
+    /// connections in the order they were made, shortest first, so the k-th merge is `edges[k]`
+    pub edges: Vec<Edge>,
+    pub total_length: f64,
+}
+
+impl SpanningTree {
+    /// One line per merge, in order, with both boxes' coordinates
+    pub fn write_csv(&self, out: &mut impl Write, points: &[Point3D]) -> io::Result<()> {
+        writeln!(out, "k,a,b,ax,ay,az,bx,by,bz,dist_sq,length,size")?;
+        for (k, edge) in self.edges.iter().enumerate() {
+            let (Point3D(ax, ay, az), Point3D(bx, by, bz)) = (points[edge.a], points[edge.b]);
+            writeln!(
+                out,
+                "{k},{},{},{ax},{ay},{az},{bx},{by},{bz},{},{:.3},{}",
+                edge.a,
+                edge.b,
+                edge.dist_sq,
+                edge.length(),
+                edge.size
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Kruskal's algorithm: connect the closest pairs first, skipping pairs already on the same
+/// circuit, until every box is on one circuit
+pub fn minimum_spanning_tree(points: &[Point3D]) -> SpanningTree {
+    let mut distances_sq = get_distances_sq(points);
+    distances_sq.sort();
+    let mut circuits = UnionFind::new(points.len());
+    let mut edges = Vec::with_capacity(points.len().saturating_sub(1));
+    for &PairwiseDistance(dist_sq, a, b) in &distances_sq {
+        if circuits.union(a, b) {
+            let size = circuits.size(a);
+            edges.push(Edge {
+                a,
+                b,
+                dist_sq,
+                size,
+            });
+            if circuits.components() == 1 {
+                break;
+            }
+        }
+    }
+    SpanningTree {
+        total_length: edges.iter().map(Edge::length).sum(),
+        edges,
+    }
+}
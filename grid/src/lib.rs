@@ -0,0 +1,332 @@
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Write};
+use std::ops::{Index, IndexMut};
+
+/// (row, col) position in a grid
+pub type Pos = (usize, usize);
+
+const ORTHOGONAL: [(isize, isize); 4] = [(-1, 0), (0, -1), (0, 1), (1, 0)];
+
+const SURROUNDING: [(isize, isize); 8] = [
+    (-1, -1),
+    (-1, 0),
+    (-1, 1),
+    (0, -1),
+    (0, 1),
+    (1, -1),
+    (1, 0),
+    (1, 1),
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseGridError {
+    Empty,
+    Ragged {
+        line: usize,
+        expected: usize,
+        found: usize,
+    },
+}
+
+impl fmt::Display for ParseGridError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseGridError::Empty => write!(f, "grid is empty"),
+            ParseGridError::Ragged {
+                line,
+                expected,
+                found,
+            } => write!(f, "line {line} has {found} cells, expected {expected}"),
+        }
+    }
+}
+
+impl Error for ParseGridError {}
+
+/// Flat, row-major grid
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Grid<T> {
+    width: usize,
+    height: usize,
+    cells: Vec<T>,
+}
+
+impl<T: Clone> Grid<T> {
+    /// Grid with every cell `fill`. Panics if either dimension is zero.
+    pub fn new(width: usize, height: usize, fill: T) -> Self {
+        assert!(
+            width > 0 && height > 0,
+            "grid of {width}x{height} cells is empty"
+        );
+        Grid {
+            width,
+            height,
+            cells: vec![fill; width * height],
+        }
+    }
+}
+
+impl<T> Grid<T> {
+    /// Grid of `cells` in row-major order. Panics if either dimension is zero.
+    pub fn from_vec(width: usize, height: usize, cells: Vec<T>) -> Self {
+        assert!(
+            width > 0 && height > 0,
+            "grid of {width}x{height} cells is empty"
+        );
+        assert_eq!(
+            cells.len(),
+            width * height,
+            "cell count doesn't match dimensions"
+        );
+        Grid {
+            width,
+            height,
+            cells,
+        }
+    }
+
+    /// Parse a character map, one row per line, mapping each character with `cell`.
+    /// All lines must have the same length.
+    pub fn parse(input: &str, mut cell: impl FnMut(char) -> T) -> Result<Self, ParseGridError> {
+        let mut width = None;
+        let mut height = 0;
+        let mut cells = Vec::new();
+        for (i, line) in input.lines().enumerate() {
+            let before = cells.len();
+            cells.extend(line.chars().map(&mut cell));
+            let found = cells.len() - before;
+            match width {
+                None => width = Some(found),
+                Some(expected) if expected != found => {
+                    return Err(ParseGridError::Ragged {
+                        line: i + 1,
+                        expected,
+                        found,
+                    });
+                }
+                Some(_) => (),
+            }
+            height += 1;
+        }
+        match width {
+            Some(width) if width > 0 => Ok(Grid {
+                width,
+                height,
+                cells,
+            }),
+            _ => Err(ParseGridError::Empty),
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn contains(&self, (r, c): Pos) -> bool {
+        r < self.height && c < self.width
+    }
+
+    pub fn get(&self, pos: Pos) -> Option<&T> {
+        if self.contains(pos) {
+            Some(&self.cells[pos.0 * self.width + pos.1])
+        } else {
+            None
+        }
+    }
+
+    pub fn get_mut(&mut self, pos: Pos) -> Option<&mut T> {
+        if self.contains(pos) {
+            Some(&mut self.cells[pos.0 * self.width + pos.1])
+        } else {
+            None
+        }
+    }
+
+    /// Position `(dr, dc)` away from `pos`, if it lies inside the grid
+    pub fn offset(&self, (r, c): Pos, (dr, dc): (isize, isize)) -> Option<Pos> {
+        let r = r.checked_add_signed(dr)?;
+        let c = c.checked_add_signed(dc)?;
+        self.contains((r, c)).then_some((r, c))
+    }
+
+    /// Position `(dr, dc)` away from `pos`, wrapping around the edges like a torus
+    pub fn wrapping_offset(&self, (r, c): Pos, (dr, dc): (isize, isize)) -> Pos {
+        let r = (r as isize + dr).rem_euclid(self.height as isize) as usize;
+        let c = (c as isize + dc).rem_euclid(self.width as isize) as usize;
+        (r, c)
+    }
+
+    /// Up, left, right and down neighbours that lie inside the grid
+    pub fn neighbours4(&self, pos: Pos) -> impl Iterator<Item = Pos> + '_ {
+        ORTHOGONAL.iter().filter_map(move |&d| self.offset(pos, d))
+    }
+
+    /// All eight surrounding neighbours that lie inside the grid
+    pub fn neighbours8(&self, pos: Pos) -> impl Iterator<Item = Pos> + '_ {
+        SURROUNDING.iter().filter_map(move |&d| self.offset(pos, d))
+    }
+
+    pub fn row(&self, r: usize) -> &[T] {
+        &self.cells[r * self.width..(r + 1) * self.width]
+    }
+
+    pub fn row_mut(&mut self, r: usize) -> &mut [T] {
+        &mut self.cells[r * self.width..(r + 1) * self.width]
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item = &[T]> {
+        self.cells.chunks(self.width)
+    }
+
+    pub fn column(&self, c: usize) -> impl Iterator<Item = &T> {
+        assert!(c < self.width, "column {c} out of bounds");
+        self.cells[c..].iter().step_by(self.width)
+    }
+
+    /// All positions in row-major order
+    pub fn positions(&self) -> impl Iterator<Item = Pos> + use<T> {
+        let width = self.width;
+        (0..self.height).flat_map(move |r| (0..width).map(move |c| (r, c)))
+    }
+
+    /// Cells paired with their positions in row-major order
+    pub fn iter(&self) -> impl Iterator<Item = (Pos, &T)> {
+        let width = self.width;
+        self.cells
+            .iter()
+            .enumerate()
+            .map(move |(i, cell)| ((i / width, i % width), cell))
+    }
+
+    pub fn map<U>(&self, f: impl FnMut(&T) -> U) -> Grid<U> {
+        Grid {
+            width: self.width,
+            height: self.height,
+            cells: self.cells.iter().map(f).collect(),
+        }
+    }
+
+    /// Render one character per cell, one line per row
+    pub fn render(&self, mut cell: impl FnMut(&T) -> char) -> String {
+        let mut out = String::with_capacity((self.width + 1) * self.height);
+        for row in self.rows() {
+            out.extend(row.iter().map(&mut cell));
+            out.push('\n');
+        }
+        out
+    }
+
+    /// Write as a binary PPM image, each cell a `scale` by `scale` square of its colour
+    pub fn write_ppm(
+        &self,
+        out: &mut impl Write,
+        scale: usize,
+        mut colour: impl FnMut(Pos, &T) -> [u8; 3],
+    ) -> io::Result<()> {
+        let (width, height) = (self.width * scale, self.height * scale);
+        writeln!(out, "P6\n{width} {height}\n255")?;
+        for (r, row) in self.rows().enumerate() {
+            let mut line = Vec::with_capacity(width * 3);
+            for (c, cell) in row.iter().enumerate() {
+                let colour = colour((r, c), cell);
+                for _ in 0..scale {
+                    line.extend_from_slice(&colour);
+                }
+            }
+            for _ in 0..scale {
+                out.write_all(&line)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<T> Index<Pos> for Grid<T> {
+    type Output = T;
+
+    fn index(&self, (r, c): Pos) -> &T {
+        assert!(self.contains((r, c)), "position {r},{c} out of bounds");
+        &self.cells[r * self.width + c]
+    }
+}
+
+impl<T> IndexMut<Pos> for Grid<T> {
+    fn index_mut(&mut self, (r, c): Pos) -> &mut T {
+        assert!(self.contains((r, c)), "position {r},{c} out of bounds");
+        &mut self.cells[r * self.width + c]
+    }
+}
+
+impl<T: fmt::Display> fmt::Display for Grid<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for row in self.rows() {
+            for cell in row {
+                write!(f, "{cell}")?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_and_neighbours() {
+        let grid = Grid::parse("ab\ncd\nef\n", |c| c).expect("Parse Error!");
+        assert_eq!((grid.width(), grid.height()), (2, 3));
+        assert_eq!(grid[(2, 1)], 'f');
+        assert_eq!(grid.get((3, 0)), None);
+        assert_eq!(grid.row(1), ['c', 'd']);
+        assert_eq!(grid.column(0).collect::<String>(), "ace");
+        assert_eq!(
+            grid.neighbours4((0, 0)).collect::<Vec<_>>(),
+            [(0, 1), (1, 0)]
+        );
+        assert_eq!(grid.neighbours8((1, 0)).count(), 5);
+        assert_eq!(grid.wrapping_offset((0, 0), (-1, -3)), (2, 1));
+        assert_eq!(grid.to_string(), "ab\ncd\nef\n");
+    }
+
+    #[test]
+    fn ppm() {
+        let grid = Grid::parse("ab\n", |c| c == 'b').expect("Parse Error!");
+        let mut out = Vec::new();
+        grid.write_ppm(&mut out, 2, |_, &on| [u8::from(on) * 255; 3])
+            .unwrap();
+        let (header, pixels) = out.split_at(11);
+        assert_eq!(header, b"P6\n4 2\n255\n");
+        assert_eq!(pixels.len(), 4 * 2 * 3);
+        assert_eq!(
+            pixels[..12],
+            [0, 0, 0, 0, 0, 0, 255, 255, 255, 255, 255, 255]
+        );
+        assert_eq!(pixels[..12], pixels[12..]);
+    }
+
+    #[test]
+    fn parse_ragged() {
+        assert_eq!(
+            Grid::parse("abc\nde\n", |c| c),
+            Err(ParseGridError::Ragged {
+                line: 2,
+                expected: 3,
+                found: 2
+            })
+        );
+        assert_eq!(Grid::parse("", |c| c), Err(ParseGridError::Empty));
+    }
+
+    #[test]
+    #[should_panic(expected = "grid of 0x3 cells is empty")]
+    fn no_columns() {
+        Grid::<u8>::from_vec(0, 3, Vec::new());
+    }
+}
@@ -0,0 +1,321 @@
+use std::fmt;
+use std::hash::Hash;
+
+/// Integer types an [`IntervalSet`] can hold
+pub trait Integer: Copy + Ord + Hash + fmt::Debug + fmt::Display {
+    const MIN: Self;
+    const MAX: Self;
+
+    fn checked_succ(self) -> Option<Self>;
+
+    fn checked_pred(self) -> Option<Self>;
+
+    /// `end - start` for `start <= end`, which always fits in a `u128`
+    fn span(start: Self, end: Self) -> u128;
+}
+
+macro_rules! integer {
+    ($($t:ty),*) => {$(
+        impl Integer for $t {
+            const MIN: Self = <$t>::MIN;
+            const MAX: Self = <$t>::MAX;
+
+            fn checked_succ(self) -> Option<Self> {
+                self.checked_add(1)
+            }
+
+            fn checked_pred(self) -> Option<Self> {
+                self.checked_sub(1)
+            }
+
+            fn span(start: Self, end: Self) -> u128 {
+                end.abs_diff(start) as u128
+            }
+        }
+    )*};
+}
+
+integer!(u32, u64, i32, i64, i128);
+
+/// Set of integers stored as sorted, non-overlapping inclusive ranges.
+///
+/// Ranges are kept normalized: no two ranges overlap or touch, so `3-5` and `6-8` are stored as
+/// `3-8`. Every set has exactly one representation, so sets compare equal when they contain
+/// the same integers.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct IntervalSet<T = u64> {
+    ranges: Vec<(T, T)>,
+}
+
+impl<T> Default for IntervalSet<T> {
+    fn default() -> Self {
+        IntervalSet { ranges: Vec::new() }
+    }
+}
+
+impl<T: Integer> IntervalSet<T> {
+    pub fn new() -> Self {
+        IntervalSet { ranges: Vec::new() }
+    }
+
+    /// Set covering every range, which can be in any order and overlap.
+    /// Empty ranges (start after end) are ignored.
+    pub fn from_ranges(ranges: impl IntoIterator<Item = (T, T)>) -> Self {
+        let mut sorted = ranges
+            .into_iter()
+            .filter(|&(start, end)| start <= end)
+            .collect::<Vec<_>>();
+        sorted.sort_unstable();
+        let mut merged: Vec<(T, T)> = Vec::with_capacity(sorted.len());
+        for (start, end) in sorted {
+            match merged.last_mut() {
+                Some(last) if touches(last.1, start) => last.1 = last.1.max(end),
+                _ => merged.push((start, end)),
+            }
+        }
+        IntervalSet { ranges: merged }
+    }
+
+    /// The normalized ranges in increasing order
+    pub fn ranges(&self) -> &[(T, T)] {
+        &self.ranges
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    /// Number of integers in the set, `None` only for the full range of a 128 bit type
+    pub fn checked_len(&self) -> Option<u128> {
+        self.ranges.iter().try_fold(0u128, |n, &(start, end)| {
+            n.checked_add(T::span(start, end))?.checked_add(1)
+        })
+    }
+
+    /// Number of integers in the set, which needs 65 bits for the full `u64` range.
+    /// Panics if it doesn't fit in a `u128`, see [`IntervalSet::checked_len`].
+    pub fn len(&self) -> u128 {
+        self.checked_len().expect("set too large to count")
+    }
+
+    pub fn contains(&self, x: T) -> bool {
+        self.range_of(x).is_some()
+    }
+
+    /// Index of the range containing `x`, by binary search
+    pub fn range_of(&self, x: T) -> Option<usize> {
+        let i = self.ranges.partition_point(|&(_, end)| end < x);
+        (i < self.ranges.len() && self.ranges[i].0 <= x).then_some(i)
+    }
+
+    /// Add `start..=end`, merging with any ranges it overlaps or touches
+    pub fn insert(&mut self, (start, end): (T, T)) {
+        if start > end {
+            return;
+        }
+        // first range that could merge, i.e. doesn't end before start - 1
+        let lo = self.ranges.partition_point(|&(_, e)| !touches(e, start));
+        // first range starting after end + 1
+        let hi = self.ranges.partition_point(|&(s, _)| touches(end, s));
+        let merged = if lo < hi {
+            (start.min(self.ranges[lo].0), end.max(self.ranges[hi - 1].1))
+        } else {
+            (start, end)
+        };
+        self.ranges.splice(lo..hi, [merged]);
+    }
+
+    /// Take `start..=end` out of the set, splitting ranges it falls inside
+    pub fn remove(&mut self, (start, end): (T, T)) {
+        if start > end {
+            return;
+        }
+        let lo = self.ranges.partition_point(|&(_, e)| e < start);
+        let hi = self.ranges.partition_point(|&(s, _)| s <= end);
+        if lo >= hi {
+            return;
+        }
+        let mut kept = Vec::with_capacity(2);
+        let (first, last) = (self.ranges[lo], self.ranges[hi - 1]);
+        // the neighbours exist since first starts before start and last ends after end
+        if first.0 < start {
+            kept.push((first.0, start.checked_pred().unwrap()));
+        }
+        if last.1 > end {
+            kept.push((end.checked_succ().unwrap(), last.1));
+        }
+        self.ranges.splice(lo..hi, kept);
+    }
+
+    pub fn union(&self, other: &Self) -> Self {
+        IntervalSet::from_ranges(self.ranges.iter().chain(&other.ranges).copied())
+    }
+
+    pub fn intersection(&self, other: &Self) -> Self {
+        let (a, b) = (&self.ranges, &other.ranges);
+        let (mut i, mut j) = (0, 0);
+        let mut ranges = Vec::new();
+        while i < a.len() && j < b.len() {
+            let start = a[i].0.max(b[j].0);
+            let end = a[i].1.min(b[j].1);
+            if start <= end {
+                ranges.push((start, end));
+            }
+            // advance whichever range finishes first
+            if a[i].1 < b[j].1 {
+                i += 1;
+            } else {
+                j += 1;
+            }
+        }
+        // pieces of normalized sets never touch, so the result is normalized too
+        IntervalSet { ranges }
+    }
+
+    pub fn difference(&self, other: &Self) -> Self {
+        let mut result = self.clone();
+        for &range in &other.ranges {
+            result.remove(range);
+        }
+        result
+    }
+
+    /// Integers in `start..=end` that are not in the set
+    pub fn complement(&self, (start, end): (T, T)) -> Self {
+        let mut result = IntervalSet::from_ranges([(start, end)]);
+        for &range in &self.ranges {
+            result.remove(range);
+        }
+        result
+    }
+}
+
+/// Whether a range ending at `end` overlaps or touches one starting at `start`
+fn touches<T: Integer>(end: T, start: T) -> bool {
+    start.checked_pred().is_none_or(|before| end >= before)
+}
+
+impl<T: Integer> FromIterator<(T, T)> for IntervalSet<T> {
+    fn from_iter<I: IntoIterator<Item = (T, T)>>(iter: I) -> Self {
+        IntervalSet::from_ranges(iter)
+    }
+}
+
+impl<T: Integer> fmt::Display for IntervalSet<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, (start, end)) in self.ranges.iter().enumerate() {
+            if i > 0 {
+                write!(f, ",")?;
+            }
+            write!(f, "{start}-{end}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Integers `0..8`, so every set of them is a bitmask and all of them can be checked
+    const UNIVERSE: u32 = 8;
+
+    fn set(mask: u32) -> IntervalSet<u32> {
+        (0..UNIVERSE)
+            .filter(|i| mask >> i & 1 == 1)
+            .map(|i| (i, i))
+            .collect()
+    }
+
+    fn mask(set: &IntervalSet<u32>) -> u32 {
+        set.ranges()
+            .iter()
+            .flat_map(|&(s, e)| s..=e)
+            .fold(0, |mask, i| mask | 1 << i)
+    }
+
+    fn range_mask((s, e): (u32, u32)) -> u32 {
+        (s..=e).fold(0, |mask, i| mask | 1 << i)
+    }
+
+    fn normalized(set: &IntervalSet<u32>) -> bool {
+        set.ranges().iter().all(|&(s, e)| s <= e)
+            && set.ranges().windows(2).all(|w| w[0].1 + 1 < w[1].0)
+    }
+
+    #[test]
+    fn every_small_set() {
+        let all = 1 << UNIVERSE;
+        let sets = (0..all).map(set).collect::<Vec<_>>();
+        let ranges = (0..UNIVERSE)
+            .flat_map(|s| (0..UNIVERSE).map(move |e| (s, e)))
+            .collect::<Vec<_>>();
+        for (a, set_a) in sets.iter().enumerate() {
+            let a = a as u32;
+            assert!(normalized(set_a), "{set_a}");
+            assert_eq!(mask(set_a), a, "{set_a}");
+            assert_eq!(set_a.len(), u128::from(a.count_ones()));
+            for x in 0..UNIVERSE {
+                assert_eq!(set_a.contains(x), a >> x & 1 == 1, "{set_a} contains {x}");
+            }
+            for (b, set_b) in sets.iter().enumerate() {
+                let b = b as u32;
+                let union = set_a.union(set_b);
+                let intersection = set_a.intersection(set_b);
+                let difference = set_a.difference(set_b);
+                for set in [&union, &intersection, &difference] {
+                    assert!(normalized(set), "{set}");
+                }
+                assert_eq!(mask(&union), a | b, "{set_a} | {set_b}");
+                assert_eq!(mask(&intersection), a & b, "{set_a} & {set_b}");
+                assert_eq!(mask(&difference), a & !b, "{set_a} - {set_b}");
+            }
+            for &range in &ranges {
+                let (mut inserted, mut removed) = (set_a.clone(), set_a.clone());
+                inserted.insert(range);
+                removed.remove(range);
+                let complement = set_a.complement(range);
+                for set in [&inserted, &removed, &complement] {
+                    assert!(normalized(set), "{set}");
+                }
+                // ranges with start after end are empty
+                let r = if range.0 <= range.1 {
+                    range_mask(range)
+                } else {
+                    0
+                };
+                assert_eq!(mask(&inserted), a | r, "{set_a} + {range:?}");
+                assert_eq!(mask(&removed), a & !r, "{set_a} - {range:?}");
+                assert_eq!(mask(&complement), r & !a, "{set_a} within {range:?}");
+            }
+            assert_eq!(
+                IntervalSet::from_ranges(set_a.ranges().iter().rev().copied()),
+                *set_a
+            );
+        }
+    }
+
+    #[test]
+    fn extremes() {
+        let mut set = IntervalSet::from_ranges([(0, 5), (u64::MAX - 1, u64::MAX), (6, 6)]);
+        assert_eq!(set.ranges(), [(0, 6), (u64::MAX - 1, u64::MAX)]);
+        assert_eq!(set.complement((0, u64::MAX)).len(), (1 << 64) - 9);
+        set.insert((0, u64::MAX));
+        assert_eq!(set.len(), 1 << 64);
+        set.remove((0, 0));
+        set.remove((u64::MAX, u64::MAX));
+        assert_eq!(set.ranges(), [(1, u64::MAX - 1)]);
+    }
+
+    #[test]
+    fn signed_extremes() {
+        let mut set = IntervalSet::from_ranges([(i128::MIN, -1), (0, 3)]);
+        assert_eq!(set.ranges(), [(i128::MIN, 3)]);
+        assert_eq!(set.len(), (1 << 127) + 4);
+        set.insert((4, i128::MAX));
+        assert_eq!(set.checked_len(), None);
+        set.remove((-2, 2));
+        assert_eq!(set.ranges(), [(i128::MIN, -3), (3, i128::MAX)]);
+        assert_eq!(set.complement((-10, 10)).ranges(), [(-2, 2)]);
+    }
+}